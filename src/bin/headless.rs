@@ -0,0 +1,247 @@
+//! Runs the cave simulation without a window and prints where it ended up as JSON, for testing levels
+//! and mechanics on machines without a GPU.
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+use cave_escape::levels::LEVELS;
+use cave_escape::replay::{level_hash, Recording};
+use cave_escape::settings::Settings;
+use cave_escape::sim::{Input, Sim, SimEvent};
+use cave_escape::world::{parse_map, ShopItem};
+
+const USAGE: &str = "\
+Usage: headless [options]
+
+Options:
+  --level <n|file>    Campaign level number, counting from 1, or a level file
+  --inputs <script>   Inputs to feed in, see below
+  --script <file>     Read the inputs from a file instead
+  --replay <file>     Feed in a replay, on its own level unless --level is given
+  --ticks <n>         Ticks to simulate, by default as many as there are inputs
+  --seed <n>          Seed for the run, 0 by default. Nothing is random yet
+  --move-ticks <n>    Ticks between player moves
+  --turn-based        Play in turn-based mode
+
+Scripts are whitespace separated steps, each held for the given number of ticks (1 if left out):
+  right:30  up+left:5  wait:60  undo  redo  leave  buy=health";
+
+struct Options {
+    level: Option<String>,
+    inputs: Option<String>,
+    replay: Option<PathBuf>,
+    ticks: Option<usize>,
+    seed: u64,
+    move_ticks: u64,
+    turn_based: bool,
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| fail(&e));
+    let replay = options.replay.as_ref().map(|path| {
+        Recording::load(path).unwrap_or_else(|e| fail(&format!("couldn't load replay {}: {e}",path.display())))
+    });
+    let (name,data) = match (&options.level,&replay) {
+        (Some(level),_) => load_level(level).unwrap_or_else(|e| fail(&e)),
+        (None,Some(replay)) => LEVELS.iter()
+            .find(|level| level_hash(level.data) == replay.level_hash)
+            .map(|level| (level.name.to_string(),level.data.to_string()))
+            .unwrap_or_else(|| fail("the replay isn't for a campaign level, pick its level with --level")),
+        (None,None) => fail("no level given"),
+    };
+
+    let map = parse_map(&data).unwrap_or_else(|e| fail(&format!("couldn't read level {name}: {e}")));
+    let (mut inputs,mut sim) = match replay {
+        Some(replay) => {
+            let sim = Sim::new(map,replay.seed,replay.move_ticks,replay.turn_based);
+            (replay.inputs,sim)
+        }
+        None => {
+            let inputs = parse_script(options.inputs.as_deref().unwrap_or("")).unwrap_or_else(|e| fail(&e));
+            (inputs,Sim::new(map,options.seed,options.move_ticks,options.turn_based))
+        }
+    };
+    inputs.resize(options.ticks.unwrap_or(inputs.len()),Input::default());
+
+    let died = run(&mut sim,&inputs);
+
+    let player = &sim.player;
+    let (x,y) = player.tile();
+    println!(
+        "{{\"level\":{},\"ticks\":{},\"x\":{x},\"y\":{y},\"gold\":{},\"health\":{},\"keys\":{},\"fuel\":{},\"pickaxe\":{},\"escaped\":{},\"died\":{died}}}",
+        json_string(&name),sim.tick,player.gold,player.health,player.keys,player.fuel,player.pickaxe,sim.escaped,
+    );
+}
+
+/// Feeds the inputs into the sim one tick at a time. Returns true if the player died, which stops the run.
+fn run(sim: &mut Sim, inputs: &[Input]) -> bool {
+    inputs.iter().any(|input| sim.step(*input).contains(&SimEvent::Died))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    exit(2)
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options,String> {
+    let mut options = Options {
+        level: None,
+        inputs: None,
+        replay: None,
+        ticks: None,
+        seed: 0,
+        move_ticks: Settings::default().move_ticks,
+        turn_based: false,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--level" => { options.level = Some(value()?); }
+            "--inputs" => { options.inputs = Some(value()?); }
+            "--script" => {
+                let path = value()?;
+                options.inputs = Some(fs::read_to_string(&path).map_err(|e| format!("couldn't read {path}: {e}"))?);
+            }
+            "--replay" => { options.replay = Some(PathBuf::from(value()?)); }
+            "--ticks" => { options.ticks = Some(number(&value()?)?); }
+            "--seed" => { options.seed = number(&value()?)?; }
+            "--move-ticks" => { options.move_ticks = number(&value()?)?; }
+            "--turn-based" => { options.turn_based = true; }
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                exit(0)
+            }
+            _ => return Err(format!("unknown option: {arg}")),
+        }
+    }
+    Ok(options)
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T,String> {
+    value.parse().map_err(|_| format!("not a number: {value}"))
+}
+
+/// A campaign level by number, or a level file. Returns its name and data.
+fn load_level(level: &str) -> Result<(String,String),String> {
+    if let Ok(n) = level.parse::<usize>() {
+        let level = n.checked_sub(1).and_then(|i| LEVELS.get(i)).ok_or(format!("there's no level {n}"))?;
+        return Ok((level.name.to_string(),level.data.to_string()))
+    }
+    let data = fs::read_to_string(level).map_err(|e| format!("couldn't read {level}: {e}"))?;
+    Ok((level.to_string(),data))
+}
+
+/// Turns a script like `right:30 wait:10 buy=key` into one input per tick.
+fn parse_script(script: &str) -> Result<Vec<Input>,String> {
+    let mut inputs = vec![];
+    for step in script.split_whitespace() {
+        let (keys,ticks) = match step.split_once(':') {
+            Some((keys,ticks)) => (keys,number(ticks)?),
+            None => (step,1),
+        };
+        let mut input = Input::default();
+        for key in keys.split('+') {
+            match key {
+                "up" => input.up = true,
+                "down" => input.down = true,
+                "left" => input.left = true,
+                "right" => input.right = true,
+                "undo" => input.undo = true,
+                "redo" => input.redo = true,
+                "leave" => input.leave_shop = true,
+                "wait" => {}
+                _ => {
+                    let item = key.strip_prefix("buy=").and_then(ShopItem::from_name);
+                    input.buy = Some(item.ok_or(format!("unknown input: {key}"))?);
+                }
+            }
+        }
+        inputs.extend(std::iter::repeat_n(input,ticks));
+    }
+    Ok(inputs)
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}",c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Result<Options,String> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    /// Plays a script on a turn based run of the level, so every step is one move.
+    fn play(level: &str, script: &str) -> (Sim,bool) {
+        let mut sim = Sim::new(parse_map(level).unwrap(),0,1,true);
+        let died = run(&mut sim,&parse_script(script).unwrap());
+        (sim,died)
+    }
+
+    #[test]
+    fn scripts_hold_each_step() {
+        let inputs = parse_script("right:3 up+left wait:2 buy=key").unwrap();
+        assert_eq!(inputs.len(),7);
+        assert!(inputs[..3].iter().all(|i| *i == Input { right: true, ..Input::default() }));
+        assert_eq!(inputs[3],Input { up: true, left: true, ..Input::default() });
+        assert_eq!(inputs[4],Input::default());
+        assert_eq!(inputs[6].buy,Some(ShopItem::Key));
+        assert!(parse_script("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_scripts_are_errors() {
+        assert!(parse_script("jump").is_err());
+        assert!(parse_script("right:x").is_err());
+        assert!(parse_script("buy=pony").is_err());
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = args("--level 2 --seed 7 --ticks 100 --move-ticks 3 --turn-based --inputs right").unwrap();
+        assert_eq!(options.level.as_deref(),Some("2"));
+        assert_eq!((options.seed,options.ticks,options.move_ticks,options.turn_based),(7,Some(100),3,true));
+        assert_eq!(options.inputs.as_deref(),Some("right"));
+        assert!(args("--seed").is_err());
+        assert!(args("--seed x").is_err());
+        assert!(args("--fly").is_err());
+    }
+
+    #[test]
+    fn levels_by_number() {
+        assert_eq!(load_level("1").unwrap().0,LEVELS[0].name);
+        assert!(load_level("0").is_err());
+        assert!(load_level(&(LEVELS.len() + 1).to_string()).is_err());
+    }
+
+    #[test]
+    fn escapes_with_the_gold() {
+        let (sim,died) = play("5\nwwwwww\nwpg.ew\nwwwwww","right wait right wait right wait");
+        assert!(sim.escaped && !died);
+        assert_eq!(sim.player.gold,1);
+    }
+
+    #[test]
+    fn dies_on_spikes() {
+        let (sim,died) = play("5\nwwww\nwpsw\nwwww",&"right wait ".repeat(5));
+        assert!(died);
+        assert!(!sim.escaped);
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("a \"b\" \\ \n"),"\"a \\\"b\\\" \\\\ \\u000a\"");
+    }
+}
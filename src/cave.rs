@@ -1,129 +1,41 @@
 #![warn(clippy::pedantic)]
 
-use std::process::exit;
-use std::time::{Duration, Instant};
-use neo_granseal::prelude::*;
-use crate::cave_scene::Cave;
-use crate::title_scene::TitleScreen;
+use crate::scenes::{SceneId, Transition};
+use crate::stats::RunStats;
 use crate::ui::UiEvent;
 
-
-pub const TILE_WIDTH: i32 = 28;
-pub const SCREEN: Vec2 = Vec2 {
-    x: TILE_WIDTH as f32 * 30.0,
-    y: TILE_WIDTH as f32 * 30.0,
-};
-
 pub enum CaveEvent {
-    SetScene(usize),
+    /// Show a scene on top of the current one.
+    Push(SceneId),
+    /// Go back to the scene under the current one.
+    Pop,
+    /// Swap the current scene for another.
+    Replace(SceneId),
+    /// Clear the stack and show only this scene.
+    Switch(SceneId),
+    /// Sent to a scene when it ends up on top of the stack.
+    Shown,
+    /// Animate the next push, pop or replace instead of cutting straight to it.
+    Transition(Transition),
+    /// Sent to every scene when a transition starts and when it ends. Scenes that poll held keys
+    /// ignore them while it plays, the same as key events.
+    TransitionRunning(bool),
     Error,
     Ui(UiEvent),
-}
-
-#[allow(unused)]
-pub enum TileType {
-    Floor,
-    Wall,
-    Door,
-    Gold,
-    Health,
-    Warp,
-    Exit,
-    Spikes,
-}
-
-
-pub struct SceneHandler {
-    pub(crate) current: usize,
-    pub(crate) title: TitleScreen,
-    pub(crate) scene1: Cave,
-}
-
-
-
-
-
-impl NeoGransealEventHandler for SceneHandler {
-    fn event(&mut self, core: &mut NGCore, event: Event) {
-        match event {
-            Event::Load => {
-                self.title.event(core, Event::Load);
-                self.scene1.event(core,Event::Load);
-            }
-            Event::Custom(msg) => {
-                let msg = *msg.downcast::<CaveEvent>().unwrap_or(Box::new(CaveEvent::Error));
-                match msg {
-                    CaveEvent::SetScene(scene) => {self.current = scene;}
-                    CaveEvent::Error => {println!("Received a strange event, could not unwrap it."); }
-                    CaveEvent::Ui(ui) => {
-                        println!("{ui:?}");
-                        match ui {
-                            UiEvent::HoverEnter { .. } => {}
-                            UiEvent::HoverExit { .. } => {}
-                            UiEvent::MousePressed { id,.. }if id == "start".to_owned() => {
-                                core.event(CaveEvent::SetScene(1));
-                            }
-                            UiEvent::MousePressed { id,.. }if id == "exit".to_owned() => {
-                                exit(0);
-                            }
-                            UiEvent::MouseReleased { .. } => {}
-                            _ => {}
-                        }
-                    }
-                }
-            }
-            Event::Update(_) =>  {
-                match self.current {
-                    0 => {
-                        core.set_title(format!("Title: {}",core.state.fps));
-                        self.title.event(core,event);
-                    }
-                    1 => {
-                        core.set_title(format!("Cave: {}",core.state.fps));
-                        self.scene1.event(core,event);
-                    }
-                    _ => {}
-                }
-            }
-            _ => {
-                match self.current {
-                    0 => {self.title.event(core,event);}
-                    1 => {self.scene1.event(core,event);}
-                    _ => {}
-                }
-            }
-        }
-
-    }
-}
-
-
-
-#[derive(Debug)]
-pub struct Player {
-    pub pos: Vec2,
-    pub ani: Ani<Vec2>,
-    pub gold: i32,
-    pub health: i32,
-    pub frozen_timer: std::time::Instant,
-    pub freeze_time: std::time::Duration,
-}
-impl Player {
-    pub fn new() -> Self {
-        Self {
-            pos: Default::default(),
-            ani: Ani::new(0.0,1.0,vec![]),
-            gold: 0,
-            health: 0,
-            frozen_timer: Instant::now(),
-            freeze_time: Duration::from_secs_f32(0.15),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum CaveObject {
-    Gold(Vec2),
-    Door(Vec2,i32),
-    Health(Vec2),
+    /// Sent to the cave, which starts the campaign over and shows itself.
+    NewGame,
+    /// Sent to the cave, which picks up the latest save and shows itself if there is one.
+    ContinueGame,
+    /// Sent to the cave, which starts the current level over.
+    RestartLevel,
+    /// Sent to the cave, which plays just this level and shows itself.
+    PlayLevel(usize),
+    /// Sent to the results scene when the player reaches the exit.
+    RunFinished(RunStats),
+    /// Sent to the cave when the results are dismissed, it moves on to the next level.
+    NextLevel,
+    /// Sent to the cave, which saves and goes back to the title.
+    QuitToTitle,
+    /// Sent to every scene after the options change, so they can pick up the new settings.
+    SettingsChanged,
 }
@@ -1,516 +1,620 @@
-use std::collections::HashMap;
-use std::time::Instant;
-use neo_granseal::mesh::fill_path_fan;
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use neo_granseal::mesh::FillStyle;
+use neo_granseal::events::MouseButton;
 use neo_granseal::prelude::*;
-use neo_granseal::util::{LineSegment, PathBuilder, raycast};
-use crate::cave::{ CaveEvent, CaveObject, Player, SCREEN, TileType};
+use crate::cave::CaveEvent;
+use crate::cli::Args;
+use crate::entity::EntityKind;
+use crate::input::{Action, Bindings};
+use crate::levels::LEVELS;
+use crate::moves::Direction;
+use crate::path::{find_path, next_step};
+use crate::progress::Progress;
+use crate::render::{CaveRenderer, WorldView};
+use crate::replay::{level_hash, Recording};
+use crate::save::{free_slot, latest_slot, SaveGame};
+use crate::scenes::{SceneId, Transition, TRANSITION_TIME};
+use crate::settings::Settings;
+use crate::sim::{DT, Input, Rng, Sim, SimEvent};
+use crate::speedrun::{format_ticks, SpeedrunTimer};
+use crate::stats::RunStats;
+use crate::storage::ghost_path;
 use crate::TILE_WIDTH;
+use crate::ui::{Ui, UiEvent, UiLabelStyle, UiThing};
+use crate::world::{in_light, parse_map, Player, ShopItem, TileType, tile_pos, wall_collision};
+
+const LAST_RUN: &str = "replays/last_run.cavr";
+
+/// The best run so far, simulated alongside the player's.
+struct Ghost {
+    sim: Sim,
+    inputs: std::vec::IntoIter<Input>,
+}
 
 pub struct Cave {
     cam: Camera,
-    player: Player,
-    map: HashMap<(i32,i32),TileType>,
-    meshes: HashMap<&'static str,Mesh>,
-    collision: Vec<LineSegment>,
-    objects: Vec<CaveObject>,
-    font: Font,
-    images: HashMap<String,Image>,
-    colors: HashMap<&'static str,Ani<Color>>,
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    /// Launch options from the command line.
+    args: Args,
+    sim: Sim,
+    /// Real time not yet simulated, always less than one tick after an update.
+    accumulator: f32,
+    /// Shop actions from the ui, sent with the next tick's input.
+    queued: Input,
+    /// Tiles left to walk through after clicking somewhere, nearest first.
+    path: Vec<(i32,i32)>,
+    /// Index into [`LEVELS`].
+    level: usize,
+    level_name: String,
+    level_data: String,
+    timer: SpeedrunTimer,
+    recording: Recording,
+    playback: Option<std::vec::IntoIter<Input>>,
+    ghost: Option<Ghost>,
+    /// Best time for this level in ticks.
+    best: Option<usize>,
+    /// Save slot this game is written to.
+    slot: usize,
+    /// Start runs in turn-based mode, see [`Sim::turn_based`].
+    turn_based: bool,
+    /// False when playing a single level from the level select or a level file. Its exit leads back to the
+    /// level select, the run doesn't count for the speedrun timer and it's never saved, see [`Cave::save_game`].
+    campaign: bool,
+    progress: Progress,
+    stats: RunStats,
+    renderer: CaveRenderer,
+    shop: Option<UiThing>,
+    /// A scene transition is playing, held keys don't move the player.
+    transitioning: bool,
 }
-impl Default for Cave {
-    fn default() -> Self {
+impl Cave {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>, args: Args) -> Self {
         Self {
             cam: Camera::new(Vec2::ZERO),
-            player: Player::new(),
-            map: HashMap::new(),
-            meshes: HashMap::new(),
-            collision: vec![],
-            objects: vec![],
-            font: Font::new(64f32),
-            images: HashMap::new(),
-            colors: HashMap::new(),
+            settings,
+            bindings,
+            args,
+            sim: Sim::default(),
+            accumulator: 0.0,
+            queued: Input::default(),
+            path: vec![],
+            level: 0,
+            level_name: String::new(),
+            level_data: String::new(),
+            timer: SpeedrunTimer::default(),
+            recording: Recording::default(),
+            playback: None,
+            ghost: None,
+            best: None,
+            slot: 0,
+            turn_based: false,
+            campaign: true,
+            progress: Progress::default(),
+            stats: RunStats::default(),
+            renderer: CaveRenderer::default(),
+            shop: None,
+            transitioning: false,
+        }
+    }
+    fn open_shop(&mut self, core: &NGCore) {
+        let scale = self.settings.borrow().ui_scale;
+        let style = UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text: FillStyle::Solid(Color::ORANGE),
+            text_scale: 40f32 * scale,
+            ..Default::default()
+        };
+        let mut children = vec![
+            Ui::Label {
+                name: "shop_title".to_string(),
+                position: vec2(16,16),
+                text: format!("Shop - {} gold",self.sim.player.gold),
+                children: vec![],
+                style: UiLabelStyle { text: FillStyle::Solid(Color::YELLOW), text_scale: 48f32 * scale, ..Default::default() },
+            }
+        ];
+        let mut y = 80f32 * scale;
+        self.sim.prices.iter().for_each(|(item,price)| {
+            children.push(Ui::Label {
+                name: format!("buy_{}",item.name()),
+                position: vec2(32f32,y),
+                text: format!("{} - {}g",item.label(),price),
+                children: vec![],
+                style,
+            });
+            y += 48f32 * scale;
+        });
+        children.push(Ui::Label {
+            name: "shop_leave".to_string(),
+            position: vec2(32f32,y + 16f32 * scale),
+            text: "Leave".to_string(),
+            children: vec![],
+            style,
+        });
+        let size = vec2(400f32 * scale,y + 80f32 * scale);
+        let mut shop = UiThing::default();
+        shop.build(&Ui::Frame {
+            name: "shop".to_string(),
+            position: (vec2(core.config.width,core.config.height) - size) / 2f32,
+            size,
+            children,
+            style: Default::default(),
+        });
+        self.shop = Some(shop);
+    }
+    /// (Re)starts the current level from scratch with the given seed, and starts recording it.
+    fn start_run(&mut self, seed: u64) {
+        let map = match parse_map(&self.level_data) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("Couldn't load level {}: {e}",self.level_name);
+                return
+            }
+        };
+        self.stats = RunStats::new(self.level_name.clone(),&map.entities,map.stars.clone());
+        let settings = *self.settings.borrow();
+        self.sim = Sim::new(map,seed,settings.move_ticks,self.turn_based);
+        self.accumulator = 0.0;
+        self.queued = Input::default();
+        self.path.clear();
+        self.shop = None;
+        self.recording = Recording::new(level_hash(&self.level_data),seed,self.turn_based,settings.move_ticks);
+        // Ghosts are for racing, a turn-based run has nothing to race.
+        let ghost = Recording::load(ghost_path(self.recording.level_hash)).ok().filter(|_| !self.turn_based);
+        self.best = ghost.as_ref().map(|g| g.inputs.len());
+        self.ghost = ghost.map(|recording| {
+            let mut sim = self.sim.clone();
+            sim.rng = Rng::new(recording.seed);
+            sim.player.freeze_ticks = recording.move_ticks;
+            Ghost { sim, inputs: recording.inputs.into_iter() }
+        });
+
+        self.renderer.level(&self.level_data,&self.sim,settings.soft_light);
+    }
+    fn load_level(&mut self, level: usize) {
+        self.level = level;
+        self.level_name = LEVELS[level].name.to_string();
+        self.level_data = LEVELS[level].data.to_string();
+        self.start_run(self.seed());
+    }
+    /// Plays a level file from outside the campaign, the same way as a single level from the level select.
+    fn load_file(&mut self, path: &Path) -> bool {
+        match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|data| parse_map(&data).map(|_| data)) {
+            Ok(data) => {
+                self.campaign = false;
+                self.level_name = path.file_stem().map_or("Custom Level".to_string(),|name| name.to_string_lossy().to_string());
+                self.level_data = data;
+                self.start_run(self.seed());
+                true
+            }
+            Err(e) => {
+                println!("Couldn't load level {}: {e}",path.display());
+                false
+            }
         }
     }
+    /// Seed for a fresh run, fixed if one was given on the command line.
+    fn seed(&self) -> u64 {
+        self.args.seed.unwrap_or_else(new_seed)
+    }
+    /// Keeps the run as the new ghost if it beat the best time and shows the results.
+    fn escaped(&mut self, core: &mut NGCore) {
+        let ticks = self.recording.inputs.len();
+        if !self.turn_based && self.best.is_none_or(|best| ticks < best) {
+            if let Err(e) = self.recording.save(ghost_path(self.recording.level_hash)) {
+                println!("Couldn't save ghost: {e}");
+            }
+        }
+        self.progress.complete(self.recording.level_hash,self.stats.gold);
+        if let Err(e) = self.progress.save() {
+            println!("Couldn't save progress: {e}");
+        }
+        self.stats.ticks = ticks as u64;
+        core.event(CaveEvent::RunFinished(self.stats.clone()));
+        core.event(CaveEvent::Push(SceneId::Results));
+    }
+    /// Leaves the results: splits and moves on to the next level, or back to the level select.
+    /// After the last level the run is over and the campaign starts again.
+    fn next_level(&mut self, core: &mut NGCore) {
+        if !self.campaign {
+            // On top of the title again, so backing out of the level select leads somewhere.
+            core.event(CaveEvent::Transition(Transition::fade(TRANSITION_TIME)));
+            core.event(CaveEvent::Switch(SceneId::Title));
+            core.event(CaveEvent::Push(SceneId::LevelSelect));
+            self.start_run(self.seed());
+            return
+        }
+        self.timer.split();
+        if self.level + 1 < LEVELS.len() {
+            self.load_level(self.level + 1);
+        } else {
+            if !self.turn_based && self.timer.finish() {
+                println!("New personal best: {}",format_ticks(self.timer.ticks));
+            }
+            self.timer.reset();
+            self.load_level(0);
+        }
+    }
+    /// Saves and closes in on the player on the way back to the title.
+    fn quit_to_title(&mut self, core: &mut NGCore) {
+        self.save_game();
+        let player = self.sim.player.ani.ani(self.sim.time()) + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32 - self.cam.get_offset();
+        core.event(CaveEvent::Transition(Transition::iris(player,TRANSITION_TIME)));
+        core.event(CaveEvent::Replace(SceneId::Title));
+    }
+    /// Starts the campaign over in a fresh save slot.
+    pub fn new_game(&mut self) {
+        self.campaign = true;
+        self.slot = free_slot();
+        self.timer.reset();
+        self.load_level(0);
+    }
+    /// Only campaign runs are saved. A single level would come back as a campaign run on Continue.
+    pub fn save_game(&self) {
+        if !self.campaign { return }
+        let save = SaveGame {
+            level: self.level,
+            ticks: self.timer.ticks,
+            splits: self.timer.splits.clone(),
+            player: self.sim.player.tile(),
+            gold: self.sim.player.gold,
+            health: self.sim.player.health,
+            fuel: self.sim.player.fuel,
+            keys: self.sim.player.keys,
+            pickaxe: self.sim.player.pickaxe,
+            tiles: self.sim.map.clone(),
+            // Enemies start over from where the level puts them.
+            entities: self.sim.entities.iter().filter(|e| e.enemy().is_none()).map(|e| (e.tile,e.kind)).collect(),
+        };
+        match save.save(self.slot) {
+            Ok(()) => println!("Saved game to slot {}",self.slot + 1),
+            Err(e) => println!("Couldn't save game: {e}"),
+        }
+    }
+    /// Picks up the most recently saved game. Returns false if there's nothing to continue.
+    pub fn continue_game(&mut self) -> bool {
+        let Some(slot) = latest_slot() else { return false };
+        let save = match SaveGame::load(slot) {
+            Ok(save) if save.level < LEVELS.len() => save,
+            Ok(_) => { println!("Save in slot {} is for a level that doesn't exist",slot + 1); return false }
+            Err(e) => { println!("Couldn't load save: {e}"); return false }
+        };
+        self.slot = slot;
+        self.campaign = true;
+        self.level = save.level;
+        self.level_name = LEVELS[save.level].name.to_string();
+        self.level_data = LEVELS[save.level].data.to_string();
+        self.start_run(self.seed());
+        let dug = self.sim.map.iter()
+            .filter(|(pos,t)| **t == TileType::Rock && save.tiles.get(pos) == Some(&TileType::Floor))
+            .map(|(pos,_)| *pos)
+            .collect::<Vec<_>>();
+        self.sim.map = save.tiles;
+        self.sim.collision = wall_collision(&self.sim.map);
+        self.sim.entities.retain(|e| e.enemy().is_some() || save.entities.contains(&(e.tile,e.kind)));
+        self.stats.rewound(&self.sim.entities);
+        dug.into_iter().for_each(|pos| self.renderer.dug(pos,&self.sim.map));
+        let pos = tile_pos(save.player);
+        self.sim.player = Player {
+            pos,
+            ani: Ani::new(0.0,1.0,vec![pos]),
+            gold: save.gold,
+            health: save.health,
+            fuel: save.fuel,
+            keys: save.keys,
+            pickaxe: save.pickaxe,
+            freeze_ticks: self.settings.borrow().move_ticks,
+            ..Player::new()
+        };
+        // A run picked up halfway through can't be a ghost for the whole level.
+        self.ghost = None;
+        self.best = Some(0);
+        self.timer.reset();
+        self.timer.ticks = save.ticks;
+        self.timer.splits = save.splits;
+        true
+    }
+    /// Restarts the level the recording was made on and feeds it back in instead of the keyboard.
+    fn play(&mut self, recording: Recording) {
+        if recording.level_hash != level_hash(&self.level_data) {
+            println!("Replay was recorded on a different level, not playing it.");
+            return
+        }
+        self.turn_based = recording.turn_based;
+        self.start_run(recording.seed);
+        self.sim.player.freeze_ticks = recording.move_ticks;
+        self.recording.move_ticks = recording.move_ticks;
+        self.playback = Some(recording.inputs.into_iter());
+    }
+    /// Loads a replay and plays it, switching to the campaign level it was recorded on if it's one of them.
+    fn play_file(&mut self, path: &Path) {
+        let recording = match Recording::load(path) {
+            Ok(recording) => recording,
+            Err(e) => { println!("Couldn't load replay: {e}"); return }
+        };
+        if recording.level_hash != level_hash(&self.level_data) {
+            if let Some(level) = LEVELS.iter().position(|level| level_hash(level.data) == recording.level_hash) {
+                self.campaign = false;
+                self.load_level(level);
+            }
+        }
+        self.play(recording);
+    }
+    fn input(&mut self, core: &NGCore) -> Input {
+        if let Some(input) = self.playback.as_mut().and_then(Iterator::next) {
+            return input
+        }
+        self.playback = None;
+        // Shop actions stay queued until it's over.
+        if self.transitioning { return Input::default() }
+        let bindings = self.bindings.borrow();
+        let mut input = Input {
+            up: bindings.held(core,Action::MoveUp),
+            down: bindings.held(core,Action::MoveDown),
+            left: bindings.held(core,Action::MoveLeft),
+            right: bindings.held(core,Action::MoveRight),
+            buy: self.queued.buy.take(),
+            leave_shop: std::mem::take(&mut self.queued.leave_shop),
+            undo: std::mem::take(&mut self.queued.undo),
+            redo: std::mem::take(&mut self.queued.redo),
+        };
+        drop(bindings);
+        // Any key takes back control from a click.
+        if input.moving() {
+            self.path.clear();
+        } else if let Some(dir) = self.path_step() {
+            input.hold(dir);
+        }
+        input
+    }
+    /// Direction of the next step along the clicked path, see [`next_step`].
+    fn path_step(&mut self) -> Option<Direction> {
+        next_step(&mut self.path,self.sim.player.tile(),|p| self.sim.walkable(p))
+    }
+    /// Starts walking to the tile under the mouse, if the player can see it and get there.
+    fn walk_to(&mut self, core: &NGCore) {
+        if self.playback.is_some() || self.sim.shopping { return }
+        let world = core.state.mouse.pos + self.cam.get_offset();
+        let tile = ((world.x / TILE_WIDTH as f32).floor() as i32,(world.y / TILE_WIDTH as f32).floor() as i32);
+        let center = tile_pos(tile) + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32;
+        if !in_light(center,&self.sim.light) || !self.sim.walkable(tile) { return }
+        self.path = find_path(self.sim.player.tile(),tile,|p| self.sim.walkable(p)).unwrap_or_default();
+    }
 }
 impl NeoGransealEventHandler for Cave {
     fn event(&mut self, core: &mut NGCore, event: Event) {
+        if let Some(shop) = &mut self.shop {
+            if shop.event(core,&event) {return}
+        }
         match event {
-            Event::KeyEvent {state,key} => {
-                if state == KeyState::Pressed && key == Key::F1 {
-                    core.event(CaveEvent::SetScene(0));
+            Event::KeyEvent {state: KeyState::Pressed,key} => {
+                let bindings = self.bindings.clone();
+                let is = |action| bindings.borrow().is(action,key);
+                if is(Action::Title) {
+                    self.quit_to_title(core);
+                }
+                if is(Action::QuickSave) {
+                    self.save_game();
+                }
+                if self.sim.shopping && is(Action::Back) {
+                    self.queued.leave_shop = true;
+                } else if is(Action::Pause) {
+                    core.event(CaveEvent::Push(SceneId::Pause));
+                }
+                if is(Action::TurnMode) {
+                    self.turn_based = !self.turn_based;
+                    self.start_run(self.seed());
+                }
+                if is(Action::Undo) {
+                    self.queued.undo = true;
+                }
+                if is(Action::Redo) {
+                    self.queued.redo = true;
+                }
+                if is(Action::SaveReplay) {
+                    match self.recording.save(LAST_RUN) {
+                        Ok(()) => println!("Saved replay to {LAST_RUN}"),
+                        Err(e) => println!("Couldn't save replay: {e}"),
+                    }
+                }
+                if is(Action::PlayReplay) {
+                    self.play_file(Path::new(LAST_RUN));
                 }
             }
-            Event::Draw => {
-                let time = core.timer.elapsed().as_secs_f32();
-                let mut g = ShapeGfx::new(core);
-                let mut mb = MeshBuilder::default();
-                g.set_offset(-self.cam.get_offset()); // now g draws in world space instead of screen space.
-
-                g.draw_mesh(&self.meshes["darkness"],self.cam.get_offset());
-                g.draw_mesh(&self.meshes["light"], self.cam.get_offset());
-                g.render_image(&self.images["base"],true);
-
-                g.draw_mesh(&self.meshes["floor"], Vec2::ZERO);
-                self.map.iter().for_each(|(p,t)|{
-                    let pos = vec2(p.0 * TILE_WIDTH,p.1 * TILE_WIDTH);
-                    match t {
-                        TileType::Gold => {
-                            mb.solid(self.colors["gold"].ani(time));
-                            mb.set_cursor(pos);
-                            mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+            Event::MousePressed { button: MouseButton::Left, state: KeyState::Pressed } => {
+                self.walk_to(core);
+            }
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    match *msg {
+                        CaveEvent::Ui(UiEvent::MousePressed { id, .. }) => {
+                            if id == "shop_leave" {
+                                self.queued.leave_shop = true;
+                            } else if let Some(item) = id.strip_prefix("buy_").and_then(ShopItem::from_name) {
+                                self.queued.buy = Some(item);
+                            }
                         }
-                        TileType::Door => {
-                            mb.solid(self.colors["door"].ani(time));
-                            mb.set_cursor(pos);
-                            mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+                        CaveEvent::NewGame => {
+                            self.new_game();
+                            core.event(CaveEvent::Transition(Transition::fade(TRANSITION_TIME)));
+                            core.event(CaveEvent::Switch(SceneId::Cave));
                         }
-                        TileType::Health => {
-                            mb.solid(self.colors["health"].ani(time));
-                            mb.set_cursor(pos);
-                            mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+                        CaveEvent::ContinueGame if self.continue_game() => {
+                            core.event(CaveEvent::Transition(Transition::fade(TRANSITION_TIME)));
+                            core.event(CaveEvent::Switch(SceneId::Cave));
                         }
-                        TileType::Spikes => {
-                            mb.solid(self.colors["spikes"].ani(time));
-                            mb.set_cursor(pos);
-                            mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+                        CaveEvent::PlayLevel(level) => {
+                            self.campaign = false;
+                            self.timer.reset();
+                            self.load_level(level);
+                            core.event(CaveEvent::Transition(Transition::fade(TRANSITION_TIME)));
+                            core.event(CaveEvent::Switch(SceneId::Cave));
+                        }
+                        CaveEvent::TransitionRunning(running) => {
+                            self.transitioning = running;
+                        }
+                        CaveEvent::NextLevel => {
+                            self.next_level(core);
+                        }
+                        CaveEvent::RestartLevel => {
+                            // The time spent so far stays on the clock, the same as dying.
+                            self.start_run(self.seed());
+                        }
+                        CaveEvent::QuitToTitle => {
+                            self.quit_to_title(core);
+                        }
+                        CaveEvent::SettingsChanged if self.shop.is_some() => {
+                            self.open_shop(core);
                         }
                         _ => {}
                     }
+                }
+            }
+            Event::Draw => {
+                let time = core.timer.elapsed().as_secs_f32();
+                let sim_time = self.sim.time() + self.accumulator;
+                let scale = self.settings.borrow().ui_scale;
+                let splits_x = core.config.width as f32 - 300f32 * scale;
+                let bottom = core.config.height as f32 - 16f32;
+                let debug_info = format!("tick {} seed {} fps {}",self.sim.tick,self.recording.seed,core.state.fps);
+                let mut g = ShapeGfx::new(core);
+                self.renderer.draw_world(&mut g,&WorldView {
+                    sim: &self.sim,
+                    ghost: self.ghost.as_ref().map(|ghost| &ghost.sim),
+                    path: &self.path,
+                    time,
+                    sim_time,
+                    offset: self.cam.get_offset(),
+                    debug: self.args.debug,
                 });
-                g.draw_mesh(&mb.build(),Vec2::ZERO);
-                g.set_tint(self.colors["player"].ani(time));
-                g.draw_mesh(&self.meshes["player"],self.player.ani.ani(time));
-                g.set_tint(self.colors["walls"].ani(time));
-                g.draw_mesh(&self.meshes["walls"], Vec2::ZERO);
-                g.draw_image(&self.images["base"],Vec2::ZERO);
-                //g.draw_mesh(&self.meshes["light"], Vec2::ZERO);
-                //g.draw_mesh(&self.meshes["debug"],Vec2::ZERO);
-
-                g.set_offset(Vec2::ZERO); // back to screen space
-                let status = self.font.text(
+                let status = self.renderer.font.text(
                     format!(
-                        "Health: {}\nGold: {}\n{},{}",
-                        self.player.health,
-                        self.player.gold,
-                        self.player.pos.x.floor() as i32 / TILE_WIDTH,
-                        self.player.pos.y.floor() as i32 / TILE_WIDTH,
-                    ).as_str(),1f32
+                        "Health: {}\nGold: {}\nKeys: {}\nFuel: {}\n{},{}{}",
+                        self.sim.player.health,
+                        self.sim.player.gold,
+                        self.sim.player.keys,
+                        self.sim.player.fuel,
+                        self.sim.player.tile().0,
+                        self.sim.player.tile().1,
+                        if self.sim.turn_based {"\nTurn-based (Z undo, Y redo)"} else {""},
+                    ).as_str(),scale
                 );
                 g.set_tint(Color::ORANGE);
                 g.draw_mesh(&status,vec2(16,16f32 + status.max_y()));
                 g.set_tint(Color::WHITE);
 
+                let mut y = 16f32;
+                for (i,level) in LEVELS.iter().enumerate() {
+                    let time = self.timer.splits.get(i).copied().or((i == self.level).then_some(self.timer.ticks));
+                    let delta = self.timer.delta(i);
+                    let line = self.renderer.font.text(
+                        format!(
+                            "{} {} {}",
+                            level.name,
+                            time.map_or("-".to_string(),format_ticks),
+                            delta.map_or(String::new(),|d| format!("{}{}",if d < 0 {"-"} else {"+"},format_ticks(d.unsigned_abs()))),
+                        ).as_str(),scale
+                    );
+                    g.set_tint(match delta {
+                        Some(d) if d < 0 => Color::GREEN,
+                        Some(d) if d > 0 => Color::RED,
+                        _ if i == self.level => Color::YELLOW,
+                        _ => Color::WHITE,
+                    });
+                    g.draw_mesh(&line,vec2(splits_x,y + line.max_y()));
+                    y += line.height() + 4f32;
+                }
+                g.set_tint(Color::WHITE);
+
+                if self.args.debug {
+                    let info = self.renderer.font.text(&debug_info,scale);
+                    g.draw_mesh(&info,vec2(16f32,bottom - info.height() + info.max_y()));
+                }
+
+                if let Some(shop) = &self.shop {
+                    let mut mb = MeshBuilder::default();
+                    shop.draw(&mut mb,&mut g);
+                    g.draw_mesh(&mb.build(),Vec2::ZERO);
+                }
+
 
                 //g.draw_mesh(&mb.build(), Vec2::ZERO);
 
             }
-            Event::Update(_) => {
-                let time = core.timer.elapsed().as_secs_f32();
-                let player = &mut self.player;
-                if player.frozen_timer.elapsed() >= player.freeze_time {
-                    let mut new_pos = player.pos;
-                    if core.key_held(Key::A) {
-                        player.frozen_timer = Instant::now();
-                        new_pos.x -= TILE_WIDTH as f32;
+            Event::Update(delta) => {
+                // Never try to catch up on more than a quarter second, or a long hitch snowballs.
+                self.accumulator = (self.accumulator + delta.as_secs_f32()).min(0.25);
+                while self.accumulator >= DT {
+                    self.accumulator -= DT;
+                    let input = self.input(core);
+                    self.recording.inputs.push(input);
+                    if input.moving() {
+                        self.timer.start();
                     }
-                    if core.key_held(Key::S) {
-                        player.frozen_timer = Instant::now();
-                        new_pos.y += TILE_WIDTH as f32;
-                    }
-                    if core.key_held(Key::D) {
-                        player.frozen_timer = Instant::now();
-                        new_pos.x += TILE_WIDTH as f32;
-                    }
-                    if core.key_held(Key::W) {
-                        player.frozen_timer = Instant::now();
-                        new_pos.y -= TILE_WIDTH as f32;
-                    }
-                    let t_pos = (new_pos.x.floor() as i32 / TILE_WIDTH,new_pos.y.floor() as i32 / TILE_WIDTH);
-                    let default = &mut TileType::Wall;
-                    let t_type = self.map.get_mut(&t_pos).unwrap_or(default);
-                    match t_type {
-                        TileType::Floor => {
-                            player.ani = Ani::new(time,player.freeze_time.as_secs_f32(),vec![player.pos,new_pos]);
-                            player.ani.repeat = false;
-                            player.pos = new_pos;
+                    self.timer.tick();
+                    if let Some(ghost) = &mut self.ghost {
+                        if let Some(input) = ghost.inputs.next() {
+                            ghost.sim.step(input);
                         }
-                        TileType::Wall => {}
-                        TileType::Door => {
-                            if player.gold >= 5 {
-                                player.gold -= 5;
-                                self.map.insert(t_pos,TileType::Floor);
-                                player.ani = Ani::new(time,player.freeze_time.as_secs_f32(),vec![player.pos,new_pos]);
-                                player.ani.repeat = false;
-                                player.pos = new_pos;
+                    }
+                    for event in self.sim.step(input) {
+                        match event {
+                            SimEvent::ShopOpened | SimEvent::Purchased(_) => { self.open_shop(core); }
+                            SimEvent::ShopClosed => { self.shop = None; }
+                            SimEvent::Dug(pos) => { self.renderer.dug(pos,&self.sim.map); }
+                            SimEvent::Moved => { self.stats.steps += 1; }
+                            SimEvent::PickedUp(EntityKind::Gold) => { self.stats.gold += 1; }
+                            SimEvent::PickedUp(_) => {}
+                            SimEvent::DoorOpened(_) => { self.stats.doors += 1; }
+                            SimEvent::Hurt => { self.stats.damage += 1; }
+                            SimEvent::Escaped => { self.escaped(core); }
+                            SimEvent::Checkpoint(_) => {}
+                            SimEvent::Respawned => {
+                                self.stats.rewound(&self.sim.entities);
+                                self.shop = None;
+                                self.path.clear();
+                                self.renderer.walls(&self.sim.map);
+                            }
+                            SimEvent::Died => { self.start_run(self.seed()); }
+                            SimEvent::Undone | SimEvent::Redone => {
+                                self.stats.rewound(&self.sim.entities);
+                                self.path.clear();
+                                self.renderer.walls(&self.sim.map);
                             }
-                        }
-                        TileType::Gold => {
-                            self.map.insert(t_pos,TileType::Floor);
-                            player.gold += 1;
-                            player.ani = Ani::new(time,player.freeze_time.as_secs_f32(),vec![player.pos,new_pos]);
-                            player.ani.repeat = false;
-                            player.pos = new_pos;
-                        }
-                        TileType::Health => {
-                            self.map.insert(t_pos,TileType::Floor);
-                            player.health += 1;
-                            player.ani = Ani::new(time,player.freeze_time.as_secs_f32(),vec![player.pos,new_pos]);
-                            player.ani.repeat = false;
-                            player.pos = new_pos;
-                        }
-                        TileType::Warp => {}
-                        TileType::Exit => {}
-                        TileType::Spikes => {
-                            player.health -= 1;
                         }
                     }
                 }
 
                 //core.set_title(format!("Cave: {} :: {}",core.state.fps,delta));
 
-                let obj_collision = self.map.iter().flat_map(|(pos,t)|{
-                    let pos = match t {
-                        TileType::Door |
-                        TileType::Spikes => {Some(vec2(pos.0 * TILE_WIDTH, pos.1 * TILE_WIDTH))}
-                        _ => {None}
-                    };
-                    if let Some(p) = pos {
-                        let top = LineSegment::new(p,p + vec2(TILE_WIDTH,0));
-                        let right = LineSegment::new(p + vec2(TILE_WIDTH,0), p + vec2(TILE_WIDTH,TILE_WIDTH));
-                        let bottom = LineSegment::new(p + vec2(TILE_WIDTH,TILE_WIDTH), p + vec2(0,TILE_WIDTH));
-                        let left = LineSegment::new(p + vec2(0,TILE_WIDTH), p);
-                        Some([top,right,bottom,left])
-                    } else {None}
-                }).flatten().collect::<Vec<_>>();
-
-                let mut collision: Vec<LineSegment> = vec![];
-                let screen = vec2(core.config.height,core.config.width);
-                collision.extend(&self.collision);
-                let start = self.player.pos - screen / 2.0;
-                collision.extend(&vec!(
-                    LineSegment::new(start,start + vec2(screen.x,0)).reverse_normal(),
-                    LineSegment::new(start + vec2(screen.x,0),start + screen).reverse_normal(),
-                    LineSegment::new(start + screen,start + vec2(0,screen.y)).reverse_normal(),
-                    LineSegment::new(start + vec2(0,screen.y),start).reverse_normal(),
-                ));
-                collision.extend(obj_collision);
-                self.meshes.insert("light", raycast_for_light(&(self.player.ani.ani(time) + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32),&collision));
-                self.cam.target(self.player.ani.ani(time) - vec2(core.config.width / 2, core.config.height / 2));
+                self.renderer.light(&self.sim.light_origin,&self.sim.light,self.settings.borrow().soft_light);
+                let time = self.sim.time() + self.accumulator;
+                self.cam.target(self.sim.player.ani.ani(time) - vec2(core.config.width / 2, core.config.height / 2));
             }
             Event::Load => {
-                self.colors.insert("floor",
-                                   Ani::new(0.0,3.0,vec![Color::rgb_u8(150,77,0)])
-                );
-                self.colors.insert("walls",
-                                   Ani::new(0.0,3.0,vec![Color::DARK_GRAY,Color::rgb_u8(100, 25,0),Color::DARK_GRAY])
-                );
-                self.colors.insert("gold",
-                                   Ani::new(0.0,3.0,vec![Color::ORANGE,Color::YELLOW,Color::ORANGE])
-                );
-                self.colors.insert("warp",
-                                   Ani::new(0.0,3.0,vec![Color::BLUE,Color::CYAN,Color::GREEN,Color::BLUE])
-                );
-                self.colors.insert("health",
-                                   Ani::new(0.0,3.0,vec![Color::GREEN,Color::WHITE,Color::rgb_u8(0,150,50),Color::GREEN])
-                );
-                self.colors.insert("blood",
-                                   Ani::new(0.0,3.0,vec![Color::new(0.25,0.0,0.0,1.0)])
-                );
-                self.colors.insert("exit",
-                                   Ani::new(0.0,3.0,vec![Color::MAGENTA,Color::WHITE,Color::MAGENTA])
-                );
-                self.colors.insert("door",
-                                   Ani::new(0.0,1.0,vec![Color::rgb_u8(130,20,0),Color::rgb_u8(170,70,20),Color::rgb_u8(130,20,0)])
-                );
-                self.colors.insert("player",
-                                   Ani::new(0.0,3.0,vec![Color::BLUE,Color::CYAN,Color::BLUE])
-                );
-                self.colors.insert("spikes",
-                                   Ani::new(0.0,1.0,vec![Color::RED,Color::BLACK,Color::ORANGE,Color::RED])
-                );
-                let mut mb = MeshBuilder::default();
-                mb.solid(Color::BLACK);
-                mb.rect(vec2(8192,8192));
-                let darkness = mb.build();
-                mb.clear_meshes();
-                self.meshes.insert("darkness",darkness);
-                let base = core.create_image(8192,8192, true);
-                self.images.insert("base".to_owned(),base);
-                let map = build_map(include_str!("../assets/map.txt"));
-                self.map = map.tiles;
-                self.meshes.insert("floor",map.floor);
-                self.meshes.insert("walls",map.walls);
-                mb.solid(Color::WHITE);
-                mb.rounded_rect(vec2(TILE_WIDTH,TILE_WIDTH),4f32);
-                self.meshes.insert("player",mb.build());
-
-                self.player.pos = map.player_start;
-                self.player.health = 5;
-                self.collision = map.collision;
-                self.collision.iter().for_each(|l|{
-                    l.visualize(&mut mb);
-                });
-                let debug = mb.build();
-                debug.buffer();
-                self.meshes.insert("debug", debug);
-                self.objects = map.objects;
-                self.meshes.insert("light", raycast_for_light(&(self.player.pos + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32),&self.collision));
-            }
-            _ => {}
-        }
-    }
-}
-
-#[derive(Default)]
-pub struct MapInfo {
-    pub(crate) tiles: HashMap<(i32, i32),TileType>,
-    pub(crate) floor: Mesh,
-    pub(crate) walls: Mesh,
-    pub(crate) collision: Vec<LineSegment>,
-    pub(crate) player_start: Vec2,
-    pub(crate) objects: Vec<CaveObject>,
-}
-
-pub fn build_map(data: &str) -> MapInfo {
-    let mut tiles = HashMap::new();
-    let door_g = data.lines().next().unwrap().split(',')
-        .map(|v| v.parse::<i32>().unwrap()).collect::<Vec<_>>();
-    let mut door = 0;
-
-    let mut player_start = Vec2::ZERO;
-    let mut objects: Vec<CaveObject> = vec![];
-
-    let mut wall_builder = MeshBuilder::default();
-    wall_builder.solid(Color::GRAY);
-
-    let mut floor_builder = MeshBuilder::default();
-
-    floor_builder.set_cursor(vec2(-1000,-1000));
-    floor_builder.solid(Color::BLACK);
-    floor_builder.rect(vec2(10000,10000));
-
-    let floor_color = Color::rgb_u8(150,77,0);
-
-    #[derive(Default)]
-    struct Cell {
-        north: Option<usize>,
-        west: Option<usize>,
-        east: Option<usize>,
-        south: Option<usize>,
-    }
-    let mut cells: HashMap<(usize,usize),Cell> = HashMap::new();
-
-    data.lines().skip(1).enumerate().for_each(|(y,line)| {
-        line.chars().enumerate().for_each(|(x,c)| {
-            let size = vec2(TILE_WIDTH,TILE_WIDTH);
-            let position = vec2(x as i32 * TILE_WIDTH, y as i32 * TILE_WIDTH);
-            wall_builder.set_cursor(position);
-            floor_builder.set_cursor(position);
-            match c {
-                'w' => {
-                    tiles.insert((x as i32,y as i32),TileType::Wall);
-                    cells.insert((x,y),Cell::default());
-                    wall_builder.push();
-                    wall_builder.solid(Color::rgb(0.05,0.09,0.06));
-                    wall_builder.rect(size);
-                    wall_builder.pop();
-                }
-                '.' => {
-                    tiles.insert((x as i32,y as i32),TileType::Floor);
-                    floor_builder.solid(floor_color); floor_builder.rect(size);
-
+                self.renderer.load(core);
+
+                self.timer = SpeedrunTimer::load();
+                self.progress = Progress::load();
+                self.slot = free_slot();
+                let file = self.args.level.clone();
+                if !file.is_some_and(|file| self.load_file(&file)) {
+                    self.load_level(0);
                 }
-                'g' => {
-                    tiles.insert((x as i32,y as i32),TileType::Gold);
-                    floor_builder.rect(size);
-                    objects.push(CaveObject::Gold(position));
+                if let Some(replay) = self.args.replay.clone() {
+                    self.play_file(&replay);
                 }
-                'd' => {
-                    tiles.insert((x as i32,y as i32),TileType::Door);
-                    floor_builder.solid(floor_color);
-                    floor_builder.rect(size);
-
-                    objects.push(CaveObject::Door(position,door_g[door]));
-                    door += 1;
-                }
-                'b' => {
-                    tiles.insert((x as i32,y as i32),TileType::Floor);
-                    floor_builder.solid(Color::new(0.25,0.0,0.0,1.0)); floor_builder.rect(size);
-                }
-                's' => {
-                    tiles.insert((x as i32,y as i32),TileType::Spikes);
-                    floor_builder.solid(Color::ORANGE_RED); floor_builder.rect(size);
-                }
-                'h' => {
-                    tiles.insert((x as i32,y as i32),TileType::Health);
-                    floor_builder.solid(floor_color);
-                    floor_builder.rect(size);
-                    objects.push(CaveObject::Health(position));
-                }
-                'p' => {
-                    tiles.insert((x as i32,y as i32),TileType::Floor);
-                    floor_builder.rect(size); player_start = position;
-                }
-                _ => { }
             }
-        });
-    });
-
-    let mut collision: Vec<LineSegment> = vec![];
-
-    data.lines().skip(1).enumerate().for_each(|(y,line)| {
-        line.chars().enumerate().filter(|(_,q)| q == &'w').for_each(|(x,_)| {
-            let (wcell,ecell,ncell,scell) = (&(x-1,y),&(x+1,y),&(x,y-1),&(x,y+1));
-            let pos = vec2(x as i32 * TILE_WIDTH, y as i32 * TILE_WIDTH);
-            let mut cell = Cell::default();
-            // Should I make a western edge?
-            if !cells.contains_key(wcell) {
-                // Is there a western edge north of me I can extend?
-                if !cells.contains_key(ncell) {
-                    // No, let's make one.
-                    let edge = LineSegment::new(pos + vec2(0,TILE_WIDTH), pos );
-                    collision.push(edge);
-                    cell.west = Some(collision.len() - 1);
-                } else {
-                    // Yes, Does it have an edge to extend?
-                    if let Some(edge) = cells.get(ncell).unwrap().west {
-                        collision[edge].begin.y += TILE_WIDTH as f32;
-                        cell.west = Some(edge);
-                    } else {
-                        // No, let's make one.
-                        let edge = LineSegment::new(pos + vec2(0,TILE_WIDTH), pos );
-                        collision.push(edge);
-                        cell.west = Some(collision.len() - 1);
-                    }
-                }
-            }
-            // Northern Edge
-            if !cells.contains_key(ncell) {
-                if !cells.contains_key(wcell) {
-                    let edge = LineSegment::new(pos , pos+ vec2(TILE_WIDTH, 0));
-                    collision.push(edge);
-                    cell.north = Some(collision.len() - 1);
-                } else if let Some(edge) = cells.get(wcell).unwrap().north {
-                    collision[edge].end.x += TILE_WIDTH as f32;
-                    cell.north = Some(edge);
-                } else {
-                    let edge = LineSegment::new(pos , pos+ vec2(TILE_WIDTH, 0));
-                    collision.push(edge);
-                    cell.north = Some(collision.len() - 1);
-                }
-            }
-            // Eastern Edge
-            if !cells.contains_key(ecell) {
-                if !cells.contains_key(ncell) {
-                    let edge = LineSegment::new(pos + vec2(TILE_WIDTH,0), pos + vec2(TILE_WIDTH, TILE_WIDTH));
-                    collision.push(edge);
-                    cell.east = Some(collision.len() - 1);
-                } else if let Some(edge) = cells.get(ncell).unwrap().east {
-                    collision[edge].end.y += TILE_WIDTH as f32;
-                    cell.east = Some(edge);
-                } else {
-                    let edge = LineSegment::new(pos + vec2(TILE_WIDTH,0), pos + vec2(TILE_WIDTH, TILE_WIDTH));
-                    collision.push(edge);
-                    cell.east = Some(collision.len() - 1);
-                }
-            }
-            // Southern Edge
-            if !cells.contains_key(scell) {
-                if !cells.contains_key(wcell) {
-                    let edge = LineSegment::new(pos + vec2(TILE_WIDTH,TILE_WIDTH), pos + vec2(0, TILE_WIDTH));
-                    collision.push(edge);
-                    cell.south = Some(collision.len() - 1);
-                } else if let Some(edge) = cells.get(wcell).unwrap().south {
-                    collision[edge].begin.x += TILE_WIDTH as f32;
-                    cell.south = Some(edge);
-                } else {
-                    let edge = LineSegment::new(pos + vec2(TILE_WIDTH,TILE_WIDTH), pos + vec2(0, TILE_WIDTH));
-                    collision.push(edge);
-                    cell.south = Some(collision.len() - 1);
-                }
-            }
-
-            cells.insert((x,y),cell);
-        });
-    });
-
-    // collision.iter().for_each(|e| {
-    //     wall_builder.set_cursor(Vec2::ZERO);
-    //     wall_builder.solid(Color::RED);
-    //     wall_builder.line(e.begin,e.end);
-    //     let nor = e.normal() * 10f32;
-    //     wall_builder.fade_left(Color::SPRING_GREEN,Color::YELLOW);
-    //     wall_builder.line(e.begin + e.axis() * (e.length()/2f32), e.begin + e.axis() * (e.length()/2f32) + nor);
-    //     wall_builder.solid(Color::GREEN);
-    //     wall_builder.set_cursor(e.begin - vec2(2,2));
-    //     wall_builder.rect(vec2(3,3));
-    //     wall_builder.set_cursor(e.end - vec2(2,2));
-    //     wall_builder.rect(vec2(3,3));
-    // });
-
-    let floor = floor_builder.build();
-    floor.buffer();
-
-    let walls = wall_builder.build();
-    walls.buffer();
-
-    MapInfo {
-        tiles,
-        floor,
-        walls,
-        collision,
-        player_start,
-        objects,
-    }
-}
-
-pub fn raycast_for_light(origin: &Vec2, walls: &[LineSegment]) -> Mesh {
-    let cull = SCREEN * 0.55;
-
-    let targets = walls.iter().map(|w|
-        w.begin
-    ).filter(|t| (t.x - origin.x).abs() <= cull.x && (t.y - origin.y).abs() <= cull.y ).collect::<Vec<_>>();
-
-    let mut hits = vec![];
-    targets.iter().for_each(|v| {
-        let target_angle = (*v - *origin).angle2();
-        let (angle1,angle2) = (target_angle - 0.0005,target_angle + 0.0005);
-        if let Some(wall_hit) = raycast(*origin,angle_vec2(angle1),walls) {
-            hits.push(wall_hit.hit);
-        }
-        if let Some(wall_hit) = raycast(*origin,angle_vec2(angle2),walls) {
-            hits.push(wall_hit.hit);
-        }
-    });
-
-    hits.sort_by(|a,b| {
-        (*origin - *a).angle2().total_cmp(&(*origin - *b).angle2())
-    });
-    let mut mb = MeshBuilder::default();
-    mb.solid(Color::TRANSPARENT);
-    mb.set_thickness(TILE_WIDTH as f32 / 1.2);
-    let mut pb = PathBuilder::default();
-    for (i, hit) in hits.iter().enumerate() {
-        if i == 0 {
-            pb.move_to(*hit);
-            mb.set_cursor(*hit);
-            mb.rect(vec2(16,16) - vec2(8,8));
-        } else {
-            pb.line_to(*hit);
-            mb.set_cursor(*hit - vec2(8,8));
-            mb.rect(vec2(16,16));
-
+            _ => {}
         }
     }
-    pb.close_path(true);
-    let path = pb.build();
-
-    mb.stroke_path(&path);
-
-    let light = fill_path_fan(&origin,&path);
-    let mut light = light.add(&mb.build());
-    //let mut light = triangle_fan(origin,hits);
+}
 
-    light.solid(Color::TRANSPARENT);
-    light
+/// Seed for a fresh run. Only this picks a seed from the clock, the run itself is deterministic.
+fn new_seed() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
 }
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use crate::scenes::SceneId;
+
+pub const USAGE: &str = "\
+Usage: cave_escape [options]
+
+Options:
+  --level <file>     Play a level file instead of the campaign, without saving
+  --seed <number>    Seed every run with this, nothing in the cave is random yet
+  --size <w>x<h>     Window size for this session, e.g. 1280x720
+  --scene <name>     Start on title, cave, levels, options or controls
+  --debug            Draw collision edges, the light outline and sim info
+  --replay <file>    Play back a replay, on the level it was recorded on
+  --help             Show this message";
+
+/// Launch options, for jumping straight into a scenario. Nothing here is saved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    pub level: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub size: Option<(i32,i32)>,
+    /// Defaults to the cave when a level or replay is given, otherwise the title, see [`Args::start_scenes`].
+    pub scene: Option<SceneId>,
+    pub debug: bool,
+    pub replay: Option<PathBuf>,
+    pub help: bool,
+}
+impl Args {
+    /// Parses the arguments after the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self,String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--level" => { parsed.level = Some(PathBuf::from(value()?)); }
+                "--seed" => {
+                    let seed = value()?;
+                    parsed.seed = Some(seed.parse().map_err(|_| format!("not a seed: {seed}"))?);
+                }
+                "--size" => {
+                    let size = value()?;
+                    parsed.size = Some(parse_size(&size).ok_or(format!("not a window size: {size}"))?);
+                }
+                "--scene" => {
+                    let scene = value()?;
+                    parsed.scene = Some(SceneId::from_arg(&scene).ok_or(format!("no scene called {scene}"))?);
+                }
+                "--debug" => { parsed.debug = true; }
+                "--replay" => { parsed.replay = Some(PathBuf::from(value()?)); }
+                "--help" | "-h" => { parsed.help = true; }
+                _ => return Err(format!("unknown option: {arg}")),
+            }
+        }
+        Ok(parsed)
+    }
+    /// The scene stack to start with. Menus go on top of the title, so backing out of them leads somewhere.
+    pub fn start_scenes(&self) -> Vec<SceneId> {
+        let scene = self.scene.unwrap_or(if self.level.is_some() || self.replay.is_some() { SceneId::Cave } else { SceneId::Title });
+        match scene {
+            SceneId::Title | SceneId::Cave => vec![scene],
+            _ => vec![SceneId::Title,scene],
+        }
+    }
+}
+
+/// Parses `1280x720`.
+fn parse_size(size: &str) -> Option<(i32,i32)> {
+    let (w,h) = size.split_once('x')?;
+    Some((w.parse().ok()?,h.parse().ok()?)).filter(|&(w,h)| w > 0 && h > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args,String> {
+        Args::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_every_option() {
+        let args = parse("--level cave.txt --seed 12 --size 1280x720 --scene options --debug --replay run.cavr").unwrap();
+        assert_eq!(args,Args {
+            level: Some(PathBuf::from("cave.txt")),
+            seed: Some(12),
+            size: Some((1280,720)),
+            scene: Some(SceneId::Options),
+            debug: true,
+            replay: Some(PathBuf::from("run.cavr")),
+            help: false,
+        });
+        assert!(parse("-h").unwrap().help);
+        assert_eq!(parse("").unwrap(),Args::default());
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert!(parse("--fly").is_err());
+        assert!(parse("cave.txt").is_err());
+        assert!(parse("--scene pause").is_err());
+        assert!(parse("--seed -1").is_err());
+    }
+
+    #[test]
+    fn options_need_values() {
+        for option in ["--level","--seed","--size","--scene","--replay"] {
+            assert!(parse(option).is_err(),"{option}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        for size in ["big","1280","1280x","x720","1280x720x2","0x720","-1280x720"] {
+            assert!(parse(&format!("--size {size}")).is_err(),"{size}");
+        }
+    }
+
+    #[test]
+    fn starts_on_the_title_or_the_cave() {
+        assert_eq!(parse("").unwrap().start_scenes(),vec![SceneId::Title]);
+        assert_eq!(parse("--level cave.txt").unwrap().start_scenes(),vec![SceneId::Cave]);
+        assert_eq!(parse("--replay run.cavr").unwrap().start_scenes(),vec![SceneId::Cave]);
+        assert_eq!(parse("--level cave.txt --scene title").unwrap().start_scenes(),vec![SceneId::Title]);
+        assert_eq!(parse("--scene controls").unwrap().start_scenes(),vec![SceneId::Title,SceneId::Controls]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use neo_granseal::mesh::{FillStyle, rect_filled};
+use neo_granseal::prelude::*;
+use crate::cave::CaveEvent;
+use crate::input::{Action, bindable, Bindings, key_name};
+use crate::scenes::{Transition, TRANSITION_TIME};
+use crate::settings::Settings;
+use crate::ui::{Ui, UiEvent, UiLabelStyle, UiThing};
+
+/// Lists every action with its keys. Clicking one waits for the next key press and binds it.
+pub struct ControlsScene {
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    /// Action waiting for a key, if any.
+    rebinding: Option<Action>,
+    ui: UiThing,
+}
+impl ControlsScene {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>) -> Self {
+        Self { settings, bindings, rebinding: None, ui: UiThing::default() }
+    }
+    fn build_ui(&mut self, core: &NGCore) {
+        let scale = self.settings.borrow().ui_scale;
+        let style = UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text: FillStyle::Solid(Color::ORANGE),
+            text_scale: 32f32 * scale,
+            ..Default::default()
+        };
+        let mut children = vec![
+            Ui::Label {
+                name: "controls_title".to_string(),
+                position: vec2(16,16),
+                text: "Controls".to_string(),
+                children: vec![],
+                style: UiLabelStyle { text: FillStyle::Solid(Color::YELLOW), text_scale: 64f32 * scale, ..Default::default() },
+            }
+        ];
+        let bindings = self.bindings.borrow();
+        let mut y = 100f32;
+        for action in Action::ALL {
+            let keys = if self.rebinding == Some(action) {
+                "press a key...".to_string()
+            } else {
+                bindings.keys(action).iter().map(|k| key_name(*k)).collect::<Vec<_>>().join(", ")
+            };
+            children.push(Ui::Label {
+                name: format!("bind_{}",action.name()),
+                position: vec2(32,y),
+                text: format!("{}: {}",action.label(),keys),
+                children: vec![],
+                style,
+            });
+            y += 40f32 * scale;
+        }
+        children.push(Ui::Label {
+            name: "controls_reset".to_string(),
+            position: vec2(32f32,y + 24f32 * scale),
+            text: "Reset to Defaults".to_string(),
+            children: vec![],
+            style,
+        });
+        children.push(Ui::Label {
+            name: "controls_back".to_string(),
+            position: vec2(32f32,y + 72f32 * scale),
+            text: "Back".to_string(),
+            children: vec![],
+            style,
+        });
+        drop(bindings);
+        let mut ui = UiThing::default();
+        ui.build(&Ui::Frame {
+            name: "controls".to_string(),
+            position: vec2(32,32),
+            size: vec2(core.config.width - 64,core.config.height - 64),
+            children,
+            style: Default::default(),
+        });
+        self.ui = ui;
+    }
+    fn save(&self) {
+        if let Err(e) = self.bindings.borrow().save() {
+            println!("Couldn't save bindings: {e}");
+        }
+    }
+}
+impl NeoGransealEventHandler for ControlsScene {
+    fn event(&mut self, core: &mut NGCore, event: Event) {
+        if let Event::KeyEvent { state: KeyState::Pressed, key } = event {
+            if let Some(action) = self.rebinding {
+                // Keys that can't be saved are ignored, the scene keeps waiting for another.
+                if !bindable(key) { return }
+                self.rebinding = None;
+                // Back cancels, unless that's what is being rebound.
+                if action == Action::Back || !self.bindings.borrow().is(Action::Back,key) {
+                    self.bindings.borrow_mut().bind(action,key);
+                    self.save();
+                }
+                self.build_ui(core);
+                return
+            }
+            if self.bindings.borrow().is(Action::Back,key) {
+                core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+                core.event(CaveEvent::Pop);
+            }
+            return
+        }
+        if self.ui.event(core,&event) {return}
+        match event {
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    if let CaveEvent::SettingsChanged = *msg {
+                        self.build_ui(core);
+                    } else if let CaveEvent::Ui(UiEvent::MousePressed { id, .. }) = *msg {
+                        if id == "controls_back" {
+                            self.rebinding = None;
+                            core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+                            core.event(CaveEvent::Pop);
+                        } else if id == "controls_reset" {
+                            *self.bindings.borrow_mut() = Bindings::default();
+                            self.save();
+                        } else if let Some(action) = id.strip_prefix("bind_").and_then(Action::from_name) {
+                            self.rebinding = Some(action);
+                        }
+                        self.build_ui(core);
+                    }
+                }
+            }
+            Event::Draw => {
+                let screen = rect_filled(vec2(0,0),vec2(core.config.width,core.config.height),FillStyle::Solid(Color::DARK_CYAN));
+                let mut g = ShapeGfx::new(core);
+                g.draw_mesh(&screen,vec2(0,0));
+
+                let mut mb = MeshBuilder::default();
+                self.ui.draw(&mut mb,&mut g);
+                g.draw_mesh(&mb.build(),Vec2::ZERO);
+            }
+            Event::Load => {
+                self.build_ui(core);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use neo_granseal::prelude::*;
+use neo_granseal::util::{LineSegment, raycast};
+use crate::path::find_path;
+use crate::world::tile_pos;
+use crate::TILE_WIDTH;
+
+/// How far away, in tiles, an enemy notices the player.
+pub const SIGHT_RANGE: f32 = 10.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EnemyKind {
+    /// Walks its patrol route, or stands guard if it has none, and chases the player when it can see them.
+    Patrol,
+    /// Creeps toward the player, but only while it's outside the light.
+    Lurker,
+}
+
+/// What an enemy decided to do this tick.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    Wait,
+    /// Walk onto this neighbouring tile.
+    To((i32,i32)),
+    /// The player is right there, hit them instead of moving.
+    Attack,
+}
+
+/// The brain of an enemy entity. Where it is and how it looks are the entity's, see [`crate::entity::Entity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+    pub route: Vec<(i32,i32)>,
+    pub waypoint: usize,
+    pub chasing: bool,
+    /// Tick the enemy may take its next step on.
+    pub next_move: u64,
+    pub move_ticks: u64,
+}
+impl Enemy {
+    pub fn new(kind: EnemyKind, route: Vec<(i32,i32)>) -> Self {
+        Self {
+            kind,
+            route,
+            waypoint: 0,
+            chasing: false,
+            next_move: 0,
+            move_ticks: 18,
+        }
+    }
+    /// Decides one step from `tile` toward the player or along the patrol route, depending on `kind`.
+    /// `lit` is whether the enemy is inside the player's light.
+    pub fn update(&mut self, tick: u64, tile: (i32,i32), player: (i32,i32), walls: &[LineSegment], lit: bool, walkable: impl Fn((i32,i32)) -> bool) -> Step {
+        if tick < self.next_move { return Step::Wait }
+        self.next_move = tick + self.move_ticks;
+
+        self.chasing = match self.kind {
+            EnemyKind::Patrol => can_see(tile_pos(tile), tile_pos(player), walls),
+            EnemyKind::Lurker => !lit,
+        };
+        let goal = if self.chasing {
+            player
+        } else if let Some(&waypoint) = self.route.get(self.waypoint) {
+            if waypoint == tile {
+                self.waypoint = (self.waypoint + 1) % self.route.len();
+                self.route[self.waypoint]
+            } else {
+                waypoint
+            }
+        } else {
+            return Step::Wait
+        };
+
+        match find_path(tile,goal,walkable).as_ref().and_then(|p| p.first()) {
+            Some(&next) if next == player => Step::Attack,
+            Some(&next) => Step::To(next),
+            None => Step::Wait,
+        }
+    }
+}
+
+/// Line of sight between the centers of two tiles, blocked by any of `walls`.
+pub fn can_see(from: Vec2, to: Vec2, walls: &[LineSegment]) -> bool {
+    let center = vec2(TILE_WIDTH,TILE_WIDTH) / 2f32;
+    let (from,to) = (from + center, to + center);
+    let delta = to - from;
+    let distance = (delta.x * delta.x + delta.y * delta.y).sqrt();
+    if distance > SIGHT_RANGE * TILE_WIDTH as f32 { return false }
+    match raycast(from,angle_vec2(delta.angle2()),walls) {
+        Some(wall) => {
+            let hit = wall.hit - from;
+            (hit.x * hit.x + hit.y * hit.y).sqrt() >= distance
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walls_block_sight() {
+        let wall = LineSegment::new(tile_pos((2,0)),tile_pos((2,3)));
+        assert!(can_see(tile_pos((1,1)),tile_pos((3,1)),&[]));
+        assert!(!can_see(tile_pos((1,1)),tile_pos((3,1)),&[wall]));
+        assert!(!can_see(tile_pos((0,0)),tile_pos((SIGHT_RANGE as i32 + 1,0)),&[]),"too far away");
+    }
+
+    #[test]
+    fn attacks_when_next_to_the_player() {
+        let mut enemy = Enemy::new(EnemyKind::Patrol,vec![]);
+        assert_eq!(enemy.update(0,(1,1),(2,1),&[],false,|_| true),Step::Attack);
+        assert!(enemy.chasing);
+        assert_eq!(enemy.update(1,(1,1),(2,1),&[],false,|_| true),Step::Wait,"still catching its breath");
+    }
+
+    #[test]
+    fn patrols_without_a_route_stand_guard() {
+        let mut enemy = Enemy::new(EnemyKind::Patrol,vec![]);
+        let wall = LineSegment::new(tile_pos((2,0)),tile_pos((2,3)));
+        assert_eq!(enemy.update(0,(1,1),(3,1),&[wall],false,|_| true),Step::Wait);
+        assert!(!enemy.chasing);
+    }
+}
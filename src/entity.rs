@@ -0,0 +1,270 @@
+//! Things in the cave that sit on top of the tiles and do something when run into: gold, doors, health, keys,
+//! traps and enemies. Every kind is put together from the same few components in [`EntityKind::spawn`],
+//! so the map parser, the sim and the renderer don't need to know about any one kind.
+use neo_granseal::prelude::*;
+use crate::enemy::{Enemy, EnemyKind};
+use crate::sim::SimEvent;
+use crate::world::{Player, tile_pos};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EntityKind {
+    Gold,
+    Door,
+    Health,
+    Key,
+    Spikes,
+    Patrol,
+    Lurker,
+}
+impl EntityKind {
+    pub const ALL: [EntityKind; 7] = [
+        EntityKind::Gold, EntityKind::Door, EntityKind::Health, EntityKind::Key,
+        EntityKind::Spikes, EntityKind::Patrol, EntityKind::Lurker,
+    ];
+    /// Character for this kind in level data and save files.
+    pub fn glyph(self) -> char {
+        match self {
+            EntityKind::Gold => 'g',
+            EntityKind::Door => 'd',
+            EntityKind::Health => 'h',
+            EntityKind::Key => 'k',
+            EntityKind::Spikes => 's',
+            EntityKind::Patrol => 'm',
+            EntityKind::Lurker => 'l',
+        }
+    }
+    pub fn from_glyph(c: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.glyph() == c)
+    }
+    /// A new entity of this kind on `tile`. Door prices and patrol routes are level data, the parser fills them in.
+    pub fn spawn(self, tile: (i32,i32)) -> Entity {
+        let (sprite,collider,behaviour) = match self {
+            EntityKind::Gold => (
+                Sprite::tile(vec![Color::ORANGE,Color::YELLOW,Color::ORANGE],3.0),
+                Collider::NONE,
+                Behaviour::Pickup(Loot { gold: 1, ..Default::default() }),
+            ),
+            EntityKind::Door => (
+                Sprite::tile(vec![Color::rgb_u8(130,20,0),Color::rgb_u8(170,70,20),Color::rgb_u8(130,20,0)],1.0),
+                Collider::WALL,
+                Behaviour::Door { cost: 5 },
+            ),
+            EntityKind::Health => (
+                Sprite::tile(vec![Color::GREEN,Color::WHITE,Color::rgb_u8(0,150,50),Color::GREEN],3.0),
+                Collider::NONE,
+                Behaviour::Pickup(Loot { health: 1, ..Default::default() }),
+            ),
+            EntityKind::Key => (
+                Sprite { inset: 7, corner: 4.0, ..Sprite::tile(vec![Color::rgb_u8(230,190,40),Color::WHITE,Color::rgb_u8(230,190,40)],2.0) },
+                Collider::NONE,
+                Behaviour::Pickup(Loot { keys: 1, ..Default::default() }),
+            ),
+            EntityKind::Spikes => (
+                Sprite::tile(vec![Color::RED,Color::BLACK,Color::ORANGE,Color::RED],1.0),
+                Collider::WALL,
+                Behaviour::Trap { damage: 1 },
+            ),
+            EntityKind::Patrol => (
+                Sprite {
+                    alert: Some(Ani::new(0.0,0.5,vec![Color::RED,Color::MAGENTA,Color::RED])),
+                    ..Sprite::enemy(vec![Color::MAGENTA,Color::rgb_u8(120,0,120),Color::MAGENTA],2.0)
+                },
+                Collider::SOLID,
+                Behaviour::Enemy(Enemy::new(EnemyKind::Patrol,vec![])),
+            ),
+            EntityKind::Lurker => (
+                Sprite::enemy(vec![Color::rgb_u8(40,40,40),Color::rgb_u8(70,0,90),Color::rgb_u8(40,40,40)],4.0),
+                Collider::SOLID,
+                Behaviour::Enemy(Enemy::new(EnemyKind::Lurker,vec![])),
+            ),
+        };
+        Entity {
+            id: 0,
+            kind: self,
+            tile,
+            ani: Ani::new(0.0,1.0,vec![tile_pos(tile)]),
+            sprite,
+            collider,
+            behaviour,
+        }
+    }
+}
+
+/// How an entity is drawn: a square in its tile, shrunk by `inset` and rounded by `corner`, in an animated color.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub color: Ani<Color>,
+    /// Used instead of `color` while the entity is alerted, like an enemy chasing the player.
+    pub alert: Option<Ani<Color>>,
+    pub inset: i32,
+    pub corner: f32,
+}
+impl Sprite {
+    fn tile(colors: Vec<Color>, duration: f32) -> Self {
+        Self { color: Ani::new(0.0,duration,colors), alert: None, inset: 0, corner: 0.0 }
+    }
+    fn enemy(colors: Vec<Color>, duration: f32) -> Self {
+        Self { inset: 3, corner: 8.0, ..Self::tile(colors,duration) }
+    }
+}
+
+/// What an entity keeps out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Collider {
+    /// Nothing can walk onto its tile. The player can still run into it and set off its [`Behaviour`].
+    pub solid: bool,
+    /// Blocks the light like a wall.
+    pub opaque: bool,
+}
+impl Collider {
+    pub const NONE: Collider = Collider { solid: false, opaque: false };
+    pub const SOLID: Collider = Collider { solid: true, opaque: false };
+    pub const WALL: Collider = Collider { solid: true, opaque: true };
+}
+
+/// What the player gets from a pickup.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Loot {
+    pub gold: i32,
+    pub health: i32,
+    pub keys: i32,
+}
+
+/// What happens when the player runs into an entity, and for enemies, what they do every tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Behaviour {
+    /// Taken when walked over.
+    Pickup(Loot),
+    /// Opens for a key, or for `cost` gold if the player has no key.
+    Door { cost: i32 },
+    /// Hurts the player for trying to step on it.
+    Trap { damage: i32 },
+    Enemy(Enemy),
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    /// Stays the same for the whole run, handed out by [`Entities::push`].
+    pub id: usize,
+    pub kind: EntityKind,
+    pub tile: (i32,i32),
+    /// Where it's drawn, following it from tile to tile.
+    pub ani: Ani<Vec2>,
+    pub sprite: Sprite,
+    pub collider: Collider,
+    pub behaviour: Behaviour,
+}
+impl Entity {
+    pub fn pos(&self) -> Vec2 {
+        tile_pos(self.tile)
+    }
+    pub fn enemy(&self) -> Option<&Enemy> {
+        match &self.behaviour {
+            Behaviour::Enemy(enemy) => Some(enemy),
+            _ => None,
+        }
+    }
+    /// Whether the sprite should use its alert color.
+    pub fn alerted(&self) -> bool {
+        self.enemy().is_some_and(|e| e.chasing)
+    }
+    /// Slides to a neighbouring tile over `duration` seconds.
+    pub fn step_to(&mut self, tile: (i32,i32), time: f32, duration: f32) {
+        self.ani = Ani::new(time,duration,vec![self.pos(),tile_pos(tile)]);
+        self.ani.repeat = false;
+        self.tile = tile;
+    }
+    /// The player ran into this. Returns true if it's used up, and the player steps onto its tile.
+    pub fn touch(&self, player: &mut Player, events: &mut Vec<SimEvent>) -> bool {
+        match self.behaviour {
+            Behaviour::Pickup(loot) => {
+                player.gold += loot.gold;
+                player.health += loot.health;
+                player.keys += loot.keys;
+                events.push(SimEvent::PickedUp(self.kind));
+                true
+            }
+            Behaviour::Door { cost } => {
+                if player.keys > 0 {
+                    player.keys -= 1;
+                } else if player.gold >= cost {
+                    player.gold -= cost;
+                } else {
+                    return false
+                }
+                events.push(SimEvent::DoorOpened(self.tile));
+                true
+            }
+            Behaviour::Trap { damage } => {
+                player.health -= damage;
+                events.push(SimEvent::Hurt);
+                false
+            }
+            Behaviour::Enemy(_) => false,
+        }
+    }
+}
+
+/// Every entity in a level, in the order they appear in the map, which is also the order of their ids.
+#[derive(Debug, Clone, Default)]
+pub struct Entities {
+    list: Vec<Entity>,
+    next_id: usize,
+}
+impl Entities {
+    pub fn push(&mut self, mut entity: Entity) {
+        entity.id = self.next_id;
+        self.next_id += 1;
+        self.list.push(entity);
+    }
+    pub fn get(&self, id: usize) -> Option<&Entity> {
+        let i = self.list.partition_point(|e| e.id < id);
+        self.list.get(i).filter(|e| e.id == id)
+    }
+    /// Sets the entity with this id, putting it back in its place if it was taken out of the level,
+    /// or taking it out if `entity` is `None`. Turns are undone and redone with this.
+    pub fn put(&mut self, id: usize, entity: Option<Entity>) {
+        let i = self.list.partition_point(|e| e.id < id);
+        let here = self.list.get(i).is_some_and(|e| e.id == id);
+        match (here,entity) {
+            (true,Some(entity)) => { self.list[i] = entity; }
+            (true,None) => { self.list.remove(i); }
+            (false,Some(entity)) => { self.list.insert(i,entity); }
+            (false,None) => {}
+        }
+    }
+    pub fn at(&self, tile: (i32,i32)) -> Option<&Entity> {
+        self.list.iter().find(|e| e.tile == tile)
+    }
+    /// Whether a solid entity is in the way on `tile`.
+    pub fn blocks(&self, tile: (i32,i32)) -> bool {
+        self.list.iter().any(|e| e.tile == tile && e.collider.solid)
+    }
+    /// The player ran into `tile`. What's there reacts, something solid before anything lying under it,
+    /// and is taken out of the level if it's used up. Returns false if it kept the player out.
+    pub fn touch(&mut self, tile: (i32,i32), player: &mut Player, events: &mut Vec<SimEvent>) -> bool {
+        let here = self.list.iter().enumerate().filter(|(_,e)| e.tile == tile);
+        let Some((i,_)) = here.max_by_key(|(_,e)| e.collider.solid) else { return true };
+        if !self.list[i].touch(player,events) { return false }
+        self.list.remove(i);
+        true
+    }
+    pub fn retain(&mut self, keep: impl FnMut(&Entity) -> bool) {
+        self.list.retain(keep);
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.list.iter()
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
+        self.list.iter_mut()
+    }
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+    pub fn count(&self, kind: EntityKind) -> usize {
+        self.list.iter().filter(|e| e.kind == kind).count()
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use crate::entity::{Entities, Entity};
+use crate::sim::Checkpoint;
+use crate::world::{Player, TileType};
+
+/// What one turn changed, enough to play it backwards or forwards again.
+#[derive(Debug, Clone)]
+pub struct Turn {
+    /// Every tile the turn changed, as it was before and after.
+    pub tiles: Vec<((i32,i32),TileType,TileType)>,
+    pub player: (Player,Player),
+    /// Every entity the turn changed by id, as it was before and after. `None` where it wasn't in the level.
+    pub entities: Vec<(usize,Option<Entity>,Option<Entity>)>,
+    /// The checkpoint the turn replaced and the one it reached, if it reached one.
+    /// Undoing past a checkpoint takes it back too, so dying doesn't respawn into an undone future.
+    pub checkpoint: Option<(Option<Checkpoint>,Checkpoint)>,
+}
+
+/// Undo and redo stacks for turn-based mode. Taking a new turn throws away anything that could be redone.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Turn>,
+    redo: Vec<Turn>,
+}
+impl History {
+    pub fn push(&mut self, turn: Turn) {
+        self.undo.push(turn);
+        self.redo.clear();
+    }
+    pub fn undo(&mut self) -> Option<Turn> {
+        let turn = self.undo.pop()?;
+        self.redo.push(turn.clone());
+        Some(turn)
+    }
+    pub fn redo(&mut self) -> Option<Turn> {
+        let turn = self.redo.pop()?;
+        self.undo.push(turn.clone());
+        Some(turn)
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Tiles that ended up different after a list of edits, each with what it was before the first edit to it.
+pub fn tile_changes(edits: &[((i32,i32),TileType)], map: &HashMap<(i32,i32),TileType>) -> Vec<((i32,i32),TileType,TileType)> {
+    let mut changes: Vec<((i32,i32),TileType,TileType)> = vec![];
+    edits.iter().for_each(|&(pos,old)| {
+        if changes.iter().all(|(p,_,_)| *p != pos) {
+            changes.push((pos,old,map[&pos]));
+        }
+    });
+    changes.retain(|(_,old,new)| old != new);
+    changes
+}
+
+/// Entities that moved, changed or came and went between two versions of the same level.
+pub fn entity_changes(before: &Entities, after: &Entities) -> Vec<(usize,Option<Entity>,Option<Entity>)> {
+    let changed = before.iter().filter_map(|b| match after.get(b.id) {
+        Some(a) if a.tile == b.tile && a.behaviour == b.behaviour => None,
+        a => Some((b.id,Some(b.clone()),a.cloned())),
+    });
+    let added = after.iter().filter(|a| before.get(a.id).is_none()).map(|a| (a.id,None,Some(a.clone())));
+    changed.chain(added).collect()
+}
@@ -0,0 +1,215 @@
+use std::fs;
+use std::path::PathBuf;
+use neo_granseal::prelude::*;
+use crate::storage::{config_dir, data_dir};
+
+/// Something the player can do, independent of which key does it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Confirm,
+    Back,
+    Pause,
+    Title,
+    Undo,
+    Redo,
+    TurnMode,
+    QuickSave,
+    SaveReplay,
+    PlayReplay,
+}
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight,
+        Action::Confirm, Action::Back, Action::Pause, Action::Title, Action::Undo, Action::Redo,
+        Action::TurnMode, Action::QuickSave, Action::SaveReplay, Action::PlayReplay,
+    ];
+    /// Name used in the bindings file and as the ui id suffix, e.g. `bind_move_up`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Confirm => "confirm",
+            Action::Back => "back",
+            Action::Pause => "pause",
+            Action::Title => "title",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::TurnMode => "turn_mode",
+            Action::QuickSave => "quick_save",
+            Action::SaveReplay => "save_replay",
+            Action::PlayReplay => "play_replay",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+            Action::Pause => "Pause",
+            Action::Title => "Title Screen",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::TurnMode => "Turn-based Mode",
+            Action::QuickSave => "Quick Save",
+            Action::SaveReplay => "Save Replay",
+            Action::PlayReplay => "Play Replay",
+        }
+    }
+    fn default_keys(self) -> Vec<Key> {
+        match self {
+            Action::MoveUp => vec![Key::W,Key::Up],
+            Action::MoveDown => vec![Key::S,Key::Down],
+            Action::MoveLeft => vec![Key::A,Key::Left],
+            Action::MoveRight => vec![Key::D,Key::Right],
+            Action::Confirm => vec![Key::Space,Key::Return],
+            Action::Back => vec![Key::Escape],
+            Action::Pause => vec![Key::Escape,Key::P],
+            Action::Title => vec![Key::F1],
+            Action::Undo => vec![Key::Z],
+            Action::Redo => vec![Key::Y],
+            Action::TurnMode => vec![Key::T],
+            Action::QuickSave => vec![Key::F5],
+            Action::SaveReplay => vec![Key::F7],
+            Action::PlayReplay => vec![Key::F8],
+        }
+    }
+}
+
+/// Every key that can be bound, the names in the bindings file are their `Debug` names.
+const KEYS: [Key; 59] = [
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    Key::Escape, Key::Space, Key::Return, Key::Back, Key::Tab, Key::Up, Key::Down, Key::Left, Key::Right,
+    Key::LShift, Key::LControl,
+];
+
+pub fn key_name(key: Key) -> String {
+    format!("{key:?}")
+}
+pub fn key_from_name(name: &str) -> Option<Key> {
+    KEYS.into_iter().find(|k| key_name(*k) == name)
+}
+/// Whether `key` can be bound, keys that can't be written to the bindings file would be lost on restart.
+pub fn bindable(key: Key) -> bool {
+    KEYS.contains(&key)
+}
+
+/// Which keys trigger each action. Saved as `action = Key, Key` lines, anything missing from the
+/// file keeps its default.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    keys: Vec<(Action,Vec<Key>)>,
+}
+impl Default for Bindings {
+    fn default() -> Self {
+        Self { keys: Action::ALL.into_iter().map(|a| (a,a.default_keys())).collect() }
+    }
+}
+impl Bindings {
+    pub fn load() -> Self {
+        // Bindings used to be kept with the saves, pick those up until they're saved in the new place.
+        fs::read_to_string(bindings_path())
+            .or_else(|_| fs::read_to_string(data_dir().join("bindings.txt")))
+            .map(|data| Self::from_text(&data)).unwrap_or_default()
+    }
+    /// Reads the bindings file. An action none of whose keys are known keeps its defaults.
+    pub fn from_text(data: &str) -> Self {
+        let mut bindings = Self::default();
+        data.lines().filter_map(|line| line.split_once('=')).for_each(|(action,keys)| {
+            let Some(action) = Action::from_name(action.trim()) else { return };
+            let keys = keys.split(',').filter_map(|k| key_from_name(k.trim())).collect::<Vec<_>>();
+            if !keys.is_empty() {
+                bindings.set(action,keys);
+            }
+        });
+        bindings
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        let data = self.keys.iter().map(|(action,keys)| {
+            format!("{} = {}",action.name(),keys.iter().map(|k| key_name(*k)).collect::<Vec<_>>().join(", "))
+        }).collect::<Vec<_>>().join("\n");
+        fs::create_dir_all(config_dir())?;
+        fs::write(bindings_path(),data)
+    }
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.keys.iter().find(|(a,_)| *a == action).map_or(&[],|(_,keys)| keys)
+    }
+    fn set(&mut self, action: Action, keys: Vec<Key>) {
+        if let Some((_,old)) = self.keys.iter_mut().find(|(a,_)| *a == action) {
+            *old = keys;
+        }
+    }
+    /// Makes `key` the first key for `action` in place of the old first key, the others stay bound.
+    /// Other actions that had `key` lose it, unless it's the only key they have left.
+    pub fn bind(&mut self, action: Action, key: Key) {
+        self.keys.iter_mut()
+            .filter(|(a,keys)| *a != action && keys.len() > 1)
+            .for_each(|(_,keys)| keys.retain(|k| *k != key));
+        let Some((_,keys)) = self.keys.iter_mut().find(|(a,_)| *a == action) else { return };
+        if let Some(i) = keys.iter().position(|k| *k == key) {
+            keys.remove(i);
+            keys.insert(0,key);
+        } else if keys.is_empty() {
+            keys.push(key);
+        } else {
+            keys[0] = key;
+        }
+    }
+    /// Whether `key` is bound to `action`, for matching key events.
+    pub fn is(&self, action: Action, key: Key) -> bool {
+        self.keys(action).contains(&key)
+    }
+    pub fn held(&self, core: &NGCore, action: Action) -> bool {
+        self.keys(action).iter().any(|k| core.key_held(*k))
+    }
+}
+
+/// Next to `settings.txt`.
+fn bindings_path() -> PathBuf {
+    config_dir().join("bindings.txt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_replaces_only_the_first_key() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::MoveUp,Key::I);
+        assert_eq!(bindings.keys(Action::MoveUp),[Key::I,Key::Up]);
+        bindings.bind(Action::MoveUp,Key::Up);
+        assert_eq!(bindings.keys(Action::MoveUp),[Key::Up,Key::I]);
+    }
+
+    #[test]
+    fn bind_never_leaves_an_action_without_keys() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::Title,Key::Escape);
+        assert_eq!(bindings.keys(Action::Back),[Key::Escape]);
+        assert_eq!(bindings.keys(Action::Pause),[Key::P]);
+        bindings.bind(Action::Title,Key::W);
+        assert_eq!(bindings.keys(Action::MoveUp),[Key::Up]);
+    }
+
+    #[test]
+    fn unknown_keys_keep_the_default() {
+        let bindings = Bindings::from_text("move_up = NotAKey\nconfirm = F2, Nope");
+        assert_eq!(bindings.keys(Action::MoveUp),[Key::W,Key::Up]);
+        assert_eq!(bindings.keys(Action::Confirm),[Key::F2]);
+    }
+}
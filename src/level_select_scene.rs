@@ -0,0 +1,218 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use neo_granseal::mesh::{FillStyle, rect_filled};
+use neo_granseal::prelude::*;
+use crate::cave::CaveEvent;
+use crate::input::{Action, Bindings};
+use crate::levels::LEVELS;
+use crate::progress::Progress;
+use crate::replay::{level_hash, Recording};
+use crate::scenes::{Transition, TRANSITION_TIME};
+use crate::settings::Settings;
+use crate::speedrun::format_ticks;
+use crate::storage::ghost_path;
+use crate::ui::{Ui, UiEvent, UiLabelStyle, UiThing};
+use crate::world::{MapInfo, parse_map, TileType};
+
+/// Size of one tile in the level previews.
+const THUMB_TILE: i32 = 3;
+
+/// Every level in the campaign with its records and a preview. Locked levels are listed but can't be picked.
+pub struct LevelSelect {
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    progress: Progress,
+    selected: usize,
+    thumbnails: Vec<Mesh>,
+    /// Top of each level's row on screen.
+    rows: Vec<f32>,
+    ui: UiThing,
+}
+impl LevelSelect {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>) -> Self {
+        Self {
+            settings,
+            bindings,
+            progress: Progress::default(),
+            selected: 0,
+            thumbnails: vec![],
+            rows: vec![],
+            ui: UiThing::default(),
+        }
+    }
+    fn build_ui(&mut self, core: &NGCore) {
+        let scale = self.settings.borrow().ui_scale;
+        let style = UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text: FillStyle::Solid(Color::ORANGE),
+            text_scale: 48f32 * scale,
+            ..Default::default()
+        };
+        let info_style = UiLabelStyle {
+            text: FillStyle::Solid(Color::WHITE),
+            hover_text: FillStyle::Solid(Color::WHITE),
+            text_scale: 28f32 * scale,
+            ..Default::default()
+        };
+        let mut children = vec![
+            Ui::Label {
+                name: "levels_title".to_string(),
+                position: vec2(16,16),
+                text: "Level Select".to_string(),
+                children: vec![],
+                style: UiLabelStyle { text: FillStyle::Solid(Color::YELLOW), text_scale: 64f32 * scale, ..Default::default() },
+            }
+        ];
+        self.rows.clear();
+        let mut y = 100f32;
+        for (i,level) in LEVELS.iter().enumerate() {
+            let hash = level_hash(level.data);
+            let info = if self.progress.unlocked(i) {
+                let best = Recording::load(ghost_path(hash)).ok().map(|ghost| ghost.inputs.len() as u64);
+                format!(
+                    "Best time: {}  Best gold: {}",
+                    best.map_or("-".to_string(),format_ticks),
+                    self.progress.best_gold(hash).map_or("-".to_string(),|g| g.to_string()),
+                )
+            } else {
+                "Locked".to_string()
+            };
+            self.rows.push(y);
+            children.push(Ui::Label {
+                name: format!("level_{i}"),
+                position: vec2(48f32,y),
+                text: level.name.to_string(),
+                children: vec![],
+                style,
+            });
+            children.push(Ui::Label {
+                name: format!("level_{i}_info"),
+                position: vec2(48f32,y + 56f32 * scale),
+                text: info,
+                children: vec![],
+                style: info_style,
+            });
+            let thumb = self.thumbnails.get(i).map_or(0f32,Mesh::height);
+            y += thumb.max(120f32 * scale) + 24f32;
+        }
+        children.push(Ui::Label {
+            name: "levels_back".to_string(),
+            position: vec2(48f32,y),
+            text: "Back".to_string(),
+            children: vec![],
+            style,
+        });
+        let mut ui = UiThing::default();
+        ui.build(&Ui::Frame {
+            name: "levels".to_string(),
+            position: vec2(32,32),
+            size: vec2(core.config.width - 64,core.config.height - 64),
+            children,
+            style: Default::default(),
+        });
+        self.ui = ui;
+    }
+    fn play(&self, core: &mut NGCore, level: usize) {
+        if self.progress.unlocked(level) {
+            core.event(CaveEvent::PlayLevel(level));
+        }
+    }
+    fn back(core: &mut NGCore) {
+        core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+        core.event(CaveEvent::Pop);
+    }
+}
+impl NeoGransealEventHandler for LevelSelect {
+    fn event(&mut self, core: &mut NGCore, event: Event) {
+        if self.ui.event(core,&event) {return}
+        match event {
+            Event::KeyEvent { state: KeyState::Pressed, key } => {
+                let bindings = self.bindings.borrow();
+                if bindings.is(Action::MoveUp,key) {
+                    self.selected = self.selected.checked_sub(1).unwrap_or(LEVELS.len() - 1);
+                } else if bindings.is(Action::MoveDown,key) {
+                    self.selected = (self.selected + 1) % LEVELS.len();
+                } else if bindings.is(Action::Confirm,key) {
+                    self.play(core,self.selected);
+                } else if bindings.is(Action::Back,key) {
+                    Self::back(core);
+                }
+            }
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    match *msg {
+                        CaveEvent::Ui(UiEvent::MousePressed { id, .. }) => {
+                            if id == "levels_back" {
+                                Self::back(core);
+                            } else if let Some(level) = id.strip_prefix("level_").and_then(|i| i.trim_end_matches("_info").parse().ok()) {
+                                self.selected = level;
+                                self.play(core,level);
+                            }
+                        }
+                        CaveEvent::Ui(UiEvent::HoverEnter { id, .. }) => {
+                            if let Some(level) = id.strip_prefix("level_").and_then(|i| i.trim_end_matches("_info").parse().ok()) {
+                                self.selected = level;
+                            }
+                        }
+                        CaveEvent::Shown => {
+                            self.progress = Progress::load();
+                            self.build_ui(core);
+                        }
+                        CaveEvent::SettingsChanged => { self.build_ui(core); }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Draw => {
+                let scale = self.settings.borrow().ui_scale;
+                let screen = rect_filled(vec2(0,0),vec2(core.config.width,core.config.height),FillStyle::Solid(Color::DARK_CYAN));
+                let thumb_x = core.config.width as f32 - 64f32;
+                let mut g = ShapeGfx::new(core);
+                g.draw_mesh(&screen,vec2(0,0));
+
+                let mut mb = MeshBuilder::default();
+                self.ui.draw(&mut mb,&mut g);
+                g.draw_mesh(&mb.build(),Vec2::ZERO);
+
+                for (i,(thumb,y)) in self.thumbnails.iter().zip(&self.rows).enumerate() {
+                    g.set_tint(if self.progress.unlocked(i) { Color::WHITE } else { Color::DIM_GRAY });
+                    g.draw_mesh(thumb,vec2(thumb_x - thumb.width(),32f32 + y));
+                }
+                g.set_tint(Color::WHITE);
+                if let Some(y) = self.rows.get(self.selected) {
+                    let marker = rect_filled(vec2(48f32,44f32 + y + 16f32 * scale),vec2(16,16),FillStyle::Solid(Color::YELLOW));
+                    g.draw_mesh(&marker,Vec2::ZERO);
+                }
+            }
+            Event::Load => {
+                self.thumbnails = LEVELS.iter().map(|level| thumbnail(&parse_map(level.data).unwrap_or_default())).collect();
+                self.progress = Progress::load();
+                self.build_ui(core);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A small picture of the level, one square per tile, with entities in the first color of their sprite.
+pub fn thumbnail(map: &MapInfo) -> Mesh {
+    let mut mb = MeshBuilder::default();
+    let mut square = |pos: (i32,i32), color: Color| {
+        mb.solid(color);
+        mb.set_cursor(vec2(pos.0 * THUMB_TILE,pos.1 * THUMB_TILE));
+        mb.rect(vec2(THUMB_TILE,THUMB_TILE));
+    };
+    map.tiles.iter().for_each(|(pos,t)| {
+        let color = match t {
+            TileType::Wall => Color::DARK_GRAY,
+            TileType::Rock => Color::rgb_u8(100,25,0),
+            TileType::Gate => Color::rgb_u8(130,20,0),
+            TileType::Exit => Color::MAGENTA,
+            TileType::Shop => Color::CYAN,
+            _ => Color::rgb_u8(150,77,0),
+        };
+        square(*pos,color);
+    });
+    map.entities.iter().for_each(|e| square(e.tile,e.sprite.color.ani(0.0)));
+    mb.build()
+}
@@ -0,0 +1,11 @@
+pub struct Level {
+    pub name: &'static str,
+    pub data: &'static str,
+}
+
+/// The campaign, in the order it's played.
+pub const LEVELS: &[Level] = &[
+    Level { name: "The Cave", data: include_str!("../assets/map.txt") },
+    Level { name: "Spike Hall", data: include_str!("../assets/map2.txt") },
+    Level { name: "Boulder Works", data: include_str!("../assets/map3.txt") },
+];
@@ -0,0 +1,30 @@
+pub mod cave;
+pub mod cave_scene;
+pub mod cli;
+pub mod controls_scene;
+mod enemy;
+mod entity;
+mod history;
+pub mod input;
+pub mod level_select_scene;
+pub mod levels;
+mod moves;
+pub mod options_scene;
+mod path;
+pub mod pause_scene;
+mod progress;
+pub mod render;
+pub mod replay;
+pub mod results_scene;
+mod save;
+pub mod scenes;
+pub mod settings;
+pub mod sim;
+mod speedrun;
+mod stats;
+mod storage;
+pub mod title_scene;
+mod ui;
+pub mod world;
+
+pub use crate::world::TILE_WIDTH;
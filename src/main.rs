@@ -1,22 +1,47 @@
-mod cave;
-mod cave_scene;
-mod title_scene;
-mod ui;
-
+use std::cell::RefCell;
+use std::rc::Rc;
 use neo_granseal::prelude::*;
-pub use crate::cave::{SceneHandler, TILE_WIDTH};
-use crate::cave_scene::Cave;
-use crate::title_scene::TitleScreen;
+use cave_escape::cave_scene::Cave;
+use cave_escape::cli::{Args, USAGE};
+use cave_escape::controls_scene::ControlsScene;
+use cave_escape::input::Bindings;
+use cave_escape::level_select_scene::LevelSelect;
+use cave_escape::options_scene::OptionsScene;
+use cave_escape::pause_scene::PauseMenu;
+use cave_escape::results_scene::ResultsScene;
+use cave_escape::scenes::{SceneHandler, SceneId};
+use cave_escape::settings::Settings;
+use cave_escape::title_scene::TitleScreen;
 
 fn main() {
-    let scene_handler = SceneHandler {
-        current: 0,
-        title: TitleScreen::default(),
-        scene1: Cave::default(),
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
     };
-    start(scene_handler, GransealGameConfig::default()
-        .size(30 * TILE_WIDTH, 30 * TILE_WIDTH)
+    if args.help {
+        println!("{USAGE}");
+        return
+    }
+    let settings = Settings::load();
+    // A size from the command line is only for this session, the options keep the saved one.
+    let (width,height) = args.size.unwrap_or((settings.width,settings.height));
+    let config = GransealGameConfig::default()
+        .size(width, height)
         .clear_color(Color::BLACK)
-        .vsync(false)
-    );
+        .vsync(settings.vsync)
+        .fullscreen(settings.fullscreen);
+    let settings = Rc::new(RefCell::new(settings));
+    let bindings = Rc::new(RefCell::new(Bindings::load()));
+    let scene_handler = SceneHandler::new(args.start_scenes())
+        .scene(SceneId::Title,TitleScreen::new(settings.clone(),bindings.clone()))
+        .scene(SceneId::Cave,Cave::new(settings.clone(),bindings.clone(),args))
+        .scene(SceneId::Controls,ControlsScene::new(settings.clone(),bindings.clone()))
+        .scene(SceneId::Pause,PauseMenu::new(settings.clone(),bindings.clone()))
+        .scene(SceneId::Options,OptionsScene::new(settings.clone(),bindings.clone()))
+        .scene(SceneId::LevelSelect,LevelSelect::new(settings.clone(),bindings.clone()))
+        .scene(SceneId::Results,ResultsScene::new(settings,bindings));
+    start(scene_handler, config);
 }
@@ -0,0 +1,125 @@
+use crate::sim::Input;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    /// One tile in this direction.
+    pub fn offset(self) -> (i32,i32) {
+        match self {
+            Direction::Up => (0,-1),
+            Direction::Down => (0,1),
+            Direction::Left => (-1,0),
+            Direction::Right => (1,0),
+        }
+    }
+    /// The direction of a step between two neighbouring tiles.
+    pub fn between(from: (i32,i32), to: (i32,i32)) -> Option<Self> {
+        let step = (to.0 - from.0, to.1 - from.1);
+        Self::ALL.into_iter().find(|d| d.offset() == step)
+    }
+    fn held(self, input: &Input) -> bool {
+        match self {
+            Direction::Up => input.up,
+            Direction::Down => input.down,
+            Direction::Left => input.left,
+            Direction::Right => input.right,
+        }
+    }
+}
+
+/// Turns held direction keys into one move at a time.
+///
+/// A key pressed while the player is still moving is remembered and used for the next move, so quick
+/// taps aren't lost. Moves are never diagonal: with several keys held, the one pressed last wins.
+#[derive(Debug, Clone, Default)]
+pub struct MoveQueue {
+    /// Directions held last tick, oldest press first.
+    held: Vec<Direction>,
+    buffered: Option<Direction>,
+}
+impl MoveQueue {
+    /// Call once per tick with that tick's input.
+    pub fn update(&mut self, input: &Input) {
+        self.held.retain(|d| d.held(input));
+        for d in Direction::ALL {
+            if d.held(input) && !self.held.contains(&d) {
+                self.held.push(d);
+                self.buffered = Some(d);
+            }
+        }
+    }
+    /// The move to make now that the player is free to move, if any.
+    pub fn next(&mut self) -> Option<Direction> {
+        self.buffered.take().or_else(|| self.held.last().copied())
+    }
+    pub fn clear(&mut self) {
+        self.buffered = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(up: bool, left: bool, right: bool) -> Input {
+        Input { up, left, right, ..Input::default() }
+    }
+
+    #[test]
+    fn tap_during_a_move_is_buffered() {
+        let mut queue = MoveQueue::default();
+        // Pressed and let go while the player is still busy moving.
+        queue.update(&held(true,false,false));
+        queue.update(&Input::default());
+        assert_eq!(queue.next(),Some(Direction::Up));
+        assert_eq!(queue.next(),None);
+    }
+
+    #[test]
+    fn last_pressed_key_wins() {
+        let mut queue = MoveQueue::default();
+        queue.update(&held(false,true,false));
+        assert_eq!(queue.next(),Some(Direction::Left));
+        queue.update(&held(true,true,false));
+        assert_eq!(queue.next(),Some(Direction::Up));
+        assert_eq!(queue.next(),Some(Direction::Up));
+        queue.update(&held(false,true,false));
+        assert_eq!(queue.next(),Some(Direction::Left));
+    }
+
+    #[test]
+    fn never_diagonal() {
+        let mut queue = MoveQueue::default();
+        queue.update(&held(true,true,true));
+        for _ in 0..3 {
+            let dir = queue.next().unwrap();
+            let (dx,dy) = dir.offset();
+            assert_eq!(dx.abs() + dy.abs(),1);
+        }
+    }
+
+    #[test]
+    fn clear_drops_the_buffered_move() {
+        let mut queue = MoveQueue::default();
+        queue.update(&held(true,false,false));
+        queue.update(&Input::default());
+        queue.clear();
+        assert_eq!(queue.next(),None);
+    }
+
+    #[test]
+    fn direction_between_neighbours() {
+        assert_eq!(Direction::between((2,2),(2,1)),Some(Direction::Up));
+        assert_eq!(Direction::between((2,2),(3,2)),Some(Direction::Right));
+        assert_eq!(Direction::between((2,2),(3,3)),None);
+        assert_eq!(Direction::between((2,2),(2,2)),None);
+        assert_eq!(Direction::between((2,2),(2,4)),None);
+    }
+}
@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use neo_granseal::mesh::{FillStyle, rect_filled};
+use neo_granseal::prelude::*;
+use crate::cave::CaveEvent;
+use crate::input::{Action, Bindings};
+use crate::scenes::{SceneId, Transition, TRANSITION_TIME};
+use crate::settings::{cycle, MOVE_SPEEDS, Settings, UI_SCALES, WINDOW_SIZES};
+use crate::ui::{Ui, UiEvent, UiLabelStyle, UiThing};
+
+/// Clicking a setting steps it to its next value and saves straight away.
+pub struct OptionsScene {
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    ui: UiThing,
+}
+impl OptionsScene {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>) -> Self {
+        Self { settings, bindings, ui: UiThing::default() }
+    }
+    fn build_ui(&mut self, core: &NGCore) {
+        let settings = *self.settings.borrow();
+        let on_off = |b: bool| if b {"On"} else {"Off"};
+        let style = UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text: FillStyle::Solid(Color::ORANGE),
+            text_scale: 40f32 * settings.ui_scale,
+            ..Default::default()
+        };
+        let items = [
+            ("options_window",format!("Window Size: {}x{} (restart)",settings.width,settings.height)),
+            ("options_vsync",format!("VSync: {} (restart)",on_off(settings.vsync))),
+            ("options_fullscreen",format!("Fullscreen: {} (restart)",on_off(settings.fullscreen))),
+            ("options_speed",format!("Movement Speed: {}",settings.move_speed_name())),
+            ("options_light",format!("Light Quality: {}",if settings.soft_light {"High"} else {"Low"})),
+            ("options_scale",format!("UI Scale: {}%",(settings.ui_scale * 100.0).round())),
+            ("options_controls","Key Bindings".to_string()),
+            ("options_back","Back".to_string()),
+        ];
+        let mut children = vec![
+            Ui::Label {
+                name: "options_title".to_string(),
+                position: vec2(16,16),
+                text: "Options".to_string(),
+                children: vec![],
+                style: UiLabelStyle { text: FillStyle::Solid(Color::YELLOW), text_scale: 64f32 * settings.ui_scale, ..Default::default() },
+            }
+        ];
+        let mut y = 100f32;
+        for (name,text) in items {
+            children.push(Ui::Label {
+                name: name.to_string(),
+                position: vec2(32f32,y),
+                text,
+                children: vec![],
+                style,
+            });
+            y += 56f32 * settings.ui_scale;
+        }
+        let mut ui = UiThing::default();
+        ui.build(&Ui::Frame {
+            name: "options".to_string(),
+            position: vec2(32,32),
+            size: vec2(core.config.width - 64,core.config.height - 64),
+            children,
+            style: Default::default(),
+        });
+        self.ui = ui;
+    }
+    fn back(core: &mut NGCore) {
+        core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+        core.event(CaveEvent::Pop);
+    }
+}
+impl NeoGransealEventHandler for OptionsScene {
+    fn event(&mut self, core: &mut NGCore, event: Event) {
+        if self.ui.event(core,&event) {return}
+        match event {
+            Event::KeyEvent { state: KeyState::Pressed, key } if self.bindings.borrow().is(Action::Back,key) => {
+                Self::back(core);
+            }
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    match *msg {
+                        CaveEvent::Ui(UiEvent::MousePressed { id, .. }) => {
+                            let mut settings = self.settings.borrow_mut();
+                            let before = *settings;
+                            match id.as_str() {
+                                "options_window" => {
+                                    (settings.width,settings.height) = cycle(&WINDOW_SIZES,(settings.width,settings.height));
+                                }
+                                "options_vsync" => { settings.vsync = !settings.vsync; }
+                                "options_fullscreen" => { settings.fullscreen = !settings.fullscreen; }
+                                "options_speed" => {
+                                    let ticks = MOVE_SPEEDS.map(|(ticks,_)| ticks);
+                                    settings.move_ticks = cycle(&ticks,settings.move_ticks);
+                                }
+                                "options_light" => { settings.soft_light = !settings.soft_light; }
+                                "options_scale" => { settings.ui_scale = cycle(&UI_SCALES,settings.ui_scale); }
+                                "options_controls" => {
+                                    core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+                                    core.event(CaveEvent::Push(SceneId::Controls));
+                                }
+                                "options_back" => { Self::back(core); }
+                                _ => {}
+                            }
+                            if *settings != before {
+                                if let Err(e) = settings.save() {
+                                    println!("Couldn't save settings: {e}");
+                                }
+                                core.event(CaveEvent::SettingsChanged);
+                            }
+                        }
+                        CaveEvent::SettingsChanged => { self.build_ui(core); }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Draw => {
+                let screen = rect_filled(vec2(0,0),vec2(core.config.width,core.config.height),FillStyle::Solid(Color::DARK_CYAN));
+                let mut g = ShapeGfx::new(core);
+                g.draw_mesh(&screen,vec2(0,0));
+
+                let mut mb = MeshBuilder::default();
+                self.ui.draw(&mut mb,&mut g);
+                g.draw_mesh(&mb.build(),Vec2::ZERO);
+            }
+            Event::Load => {
+                self.build_ui(core);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, VecDeque};
+use crate::moves::Direction;
+
+const NEIGHBOURS: [(i32,i32); 4] = [(0,-1),(1,0),(0,1),(-1,0)];
+
+/// Breadth first search over the tile grid. Returns the tiles to step through to get from `from` to `to`,
+/// not including `from`. The goal itself doesn't need to be walkable, so things can path onto the player.
+pub fn find_path(from: (i32,i32), to: (i32,i32), walkable: impl Fn((i32,i32)) -> bool) -> Option<Vec<(i32,i32)>> {
+    if from == to { return Some(vec![]) }
+    let mut came_from: HashMap<(i32,i32),(i32,i32)> = HashMap::new();
+    let mut open = VecDeque::from([from]);
+    while let Some(current) = open.pop_front() {
+        for (dx,dy) in NEIGHBOURS {
+            let next = (current.0 + dx, current.1 + dy);
+            if next == from || came_from.contains_key(&next) { continue }
+            if next != to && !walkable(next) { continue }
+            came_from.insert(next,current);
+            if next == to {
+                let mut path = vec![to];
+                let mut step = to;
+                while let Some(&prev) = came_from.get(&step) {
+                    if prev == from { break }
+                    path.push(prev);
+                    step = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            open.push_back(next);
+        }
+    }
+    None
+}
+
+/// Direction of the next step along `path` for someone standing on `tile`, dropping the step they just took.
+/// Gives up on the path if the way is blocked, a door, hazard or enemy got in the way,
+/// or they ended up somewhere else.
+pub fn next_step(path: &mut Vec<(i32,i32)>, tile: (i32,i32), walkable: impl Fn((i32,i32)) -> bool) -> Option<Direction> {
+    if path.first() == Some(&tile) {
+        path.remove(0);
+    }
+    let next = *path.first()?;
+    match Direction::between(tile,next) {
+        Some(dir) if walkable(next) => Some(dir),
+        _ => {
+            path.clear();
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A walkable grid from a picture, `#` is a wall.
+    fn grid(rows: &str) -> impl Fn((i32,i32)) -> bool + '_ {
+        move |(x,y)| rows.lines().nth(y as usize).and_then(|row| row.chars().nth(x as usize)).is_some_and(|c| c != '#')
+    }
+
+    #[test]
+    fn walks_around_walls() {
+        let walkable = grid("...\n.#.\n...");
+        let path = find_path((0,1),(2,1),walkable).unwrap();
+        assert_eq!(path.len(),4);
+        assert_eq!(path.last(),Some(&(2,1)));
+        assert!(!path.contains(&(1,1)));
+    }
+
+    #[test]
+    fn takes_the_shortest_way() {
+        let walkable = grid("....\n.##.\n....\n....");
+        assert_eq!(find_path((0,2),(3,2),&walkable),Some(vec![(1,2),(2,2),(3,2)]));
+        assert_eq!(find_path((0,0),(0,0),&walkable),Some(vec![]));
+    }
+
+    #[test]
+    fn steps_along_a_path_until_blocked() {
+        let mut path = vec![(1,0),(2,0),(3,0)];
+        assert_eq!(next_step(&mut path,(0,0),|_| true),Some(Direction::Right));
+        assert_eq!(next_step(&mut path,(1,0),|_| true),Some(Direction::Right));
+        assert_eq!(path,vec![(2,0),(3,0)]);
+        // A door or enemy turned up on the next tile.
+        assert_eq!(next_step(&mut path,(1,0),|p| p != (2,0)),None);
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn gives_up_when_knocked_off_the_path() {
+        let mut path = vec![(1,0),(2,0)];
+        assert_eq!(next_step(&mut path,(0,1),|_| true),None);
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn walled_off_goals_are_unreachable() {
+        assert_eq!(find_path((0,0),(2,0),grid(".#.")),None);
+    }
+}
@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use neo_granseal::mesh::{FillStyle, rect_filled};
+use neo_granseal::prelude::*;
+use crate::cave::CaveEvent;
+use crate::input::{Action, Bindings};
+use crate::scenes::{SceneId, Transition, TRANSITION_TIME};
+use crate::settings::Settings;
+use crate::ui::{Ui, UiEvent, UiLabelStyle, UiThing};
+
+/// Pushed over the cave, which stops updating while it's covered.
+pub struct PauseMenu {
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    ui: UiThing,
+}
+impl PauseMenu {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>) -> Self {
+        Self { settings, bindings, ui: UiThing::default() }
+    }
+    fn build_ui(&mut self, core: &NGCore) {
+        let scale = self.settings.borrow().ui_scale;
+        let style = UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text: FillStyle::Solid(Color::ORANGE),
+            text_scale: 48f32 * scale,
+            ..Default::default()
+        };
+        let mut children = vec![
+            Ui::Label {
+                name: "pause_title".to_string(),
+                position: vec2(16,16),
+                text: "Paused".to_string(),
+                children: vec![],
+                style: UiLabelStyle { text: FillStyle::Solid(Color::YELLOW), text_scale: 64f32 * scale, ..Default::default() },
+            }
+        ];
+        let items = [
+            ("pause_resume","Resume"),
+            ("pause_restart","Restart Level"),
+            ("pause_options","Options"),
+            ("pause_quit","Quit to Title"),
+        ];
+        let mut y = 100f32;
+        for (name,text) in items {
+            children.push(Ui::Label {
+                name: name.to_string(),
+                position: vec2(32f32,y),
+                text: text.to_string(),
+                children: vec![],
+                style,
+            });
+            y += 64f32 * scale;
+        }
+        let size = vec2(400f32 * scale,y + 16f32);
+        let mut ui = UiThing::default();
+        ui.build(&Ui::Frame {
+            name: "pause".to_string(),
+            position: (vec2(core.config.width,core.config.height) - size) / 2f32,
+            size,
+            children,
+            style: Default::default(),
+        });
+        self.ui = ui;
+    }
+}
+impl NeoGransealEventHandler for PauseMenu {
+    fn event(&mut self, core: &mut NGCore, event: Event) {
+        if self.ui.event(core,&event) {return}
+        match event {
+            Event::KeyEvent { state: KeyState::Pressed, key } => {
+                let bindings = self.bindings.borrow();
+                if bindings.is(Action::Pause,key) || bindings.is(Action::Back,key) {
+                    core.event(CaveEvent::Pop);
+                }
+            }
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    match *msg {
+                        CaveEvent::Ui(UiEvent::MousePressed { id, .. }) => {
+                            match id.as_str() {
+                                "pause_resume" => core.event(CaveEvent::Pop),
+                                "pause_restart" => {
+                                    core.event(CaveEvent::Pop);
+                                    core.event(CaveEvent::RestartLevel);
+                                }
+                                "pause_options" => {
+                                    core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+                                    core.event(CaveEvent::Push(SceneId::Options));
+                                }
+                                "pause_quit" => {
+                                    core.event(CaveEvent::Pop);
+                                    core.event(CaveEvent::QuitToTitle);
+                                }
+                                _ => {}
+                            }
+                        }
+                        CaveEvent::SettingsChanged => { self.build_ui(core); }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Draw => {
+                let shade = rect_filled(vec2(0,0),vec2(core.config.width,core.config.height),FillStyle::Solid(Color::new(0.0,0.0,0.0,0.6)));
+                let mut g = ShapeGfx::new(core);
+                g.draw_mesh(&shade,vec2(0,0));
+
+                let mut mb = MeshBuilder::default();
+                self.ui.draw(&mut mb,&mut g);
+                g.draw_mesh(&mb.build(),Vec2::ZERO);
+            }
+            Event::Load => {
+                self.build_ui(core);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use crate::levels::LEVELS;
+use crate::replay::level_hash;
+use crate::storage::data_dir;
+
+/// Which levels have been beaten and the most gold carried out of each. Keyed by level hash like the
+/// ghosts, so editing a level starts its record over. Best times come from the ghosts.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    best_gold: HashMap<u64,i32>,
+}
+impl Progress {
+    /// One `hash gold` line per beaten level.
+    pub fn load() -> Self {
+        let best_gold = fs::read_to_string(progress_path()).map(|data| {
+            data.lines().filter_map(|line| {
+                let (hash,gold) = line.split_once(' ')?;
+                Some((u64::from_str_radix(hash,16).ok()?,gold.trim().parse().ok()?))
+            }).collect()
+        }).unwrap_or_default();
+        Self { best_gold }
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        let data = self.best_gold.iter().map(|(hash,gold)| format!("{hash:016x} {gold}")).collect::<Vec<_>>().join("\n");
+        fs::create_dir_all(data_dir())?;
+        fs::write(progress_path(),data)
+    }
+    /// Marks the level beaten and keeps the gold if it's the most so far.
+    pub fn complete(&mut self, level_hash: u64, gold: i32) {
+        let best = self.best_gold.entry(level_hash).or_insert(gold);
+        *best = (*best).max(gold);
+    }
+    pub fn best_gold(&self, level_hash: u64) -> Option<i32> {
+        self.best_gold.get(&level_hash).copied()
+    }
+    /// The first level is always open, every other one once the level before it is beaten.
+    pub fn unlocked(&self, level: usize) -> bool {
+        level == 0 || LEVELS.get(level - 1).is_some_and(|prev| self.best_gold.contains_key(&level_hash(prev.data)))
+    }
+}
+
+fn progress_path() -> PathBuf {
+    data_dir().join("progress.txt")
+}
@@ -0,0 +1,317 @@
+//! Meshes for the cave, built from the [`world`](crate::world) state.
+use std::collections::HashMap;
+use neo_granseal::mesh::fill_path_fan;
+use neo_granseal::prelude::*;
+use neo_granseal::util::{LineSegment, PathBuilder};
+use crate::entity::EntityKind;
+use crate::sim::Sim;
+use crate::world::{light_reach, map_rows, TileType, tile_pos, TILE_WIDTH, visibility_polygon};
+
+/// What to draw for one frame of the cave.
+pub struct WorldView<'a> {
+    pub sim: &'a Sim,
+    /// The best run, racing alongside.
+    pub ghost: Option<&'a Sim>,
+    /// Tiles left to walk through after a click.
+    pub path: &'a [(i32,i32)],
+    /// Seconds since start, for the color animations.
+    pub time: f32,
+    /// Sim time including the part of a tick not simulated yet, so movement is smooth between ticks.
+    pub sim_time: f32,
+    /// Camera offset, the view is drawn in world space shifted by it.
+    pub offset: Vec2,
+    /// Draw the collision edges and the light outline.
+    pub debug: bool,
+}
+
+/// Wall tiles are meshed in squares this many tiles across, so digging one out only rebuilds its square.
+const WALL_CHUNK: i32 = 8;
+
+/// Meshes, images and colors for drawing the cave. The level meshes are rebuilt from the tile map when it changes,
+/// the rest is made once on load.
+pub struct CaveRenderer {
+    meshes: HashMap<&'static str,Mesh>,
+    /// Wall meshes by chunk, see [`wall_mesh`].
+    walls: HashMap<(i32,i32),Mesh>,
+    pub font: Font,
+    images: HashMap<String,Image>,
+    colors: HashMap<&'static str,Ani<Color>>,
+}
+impl Default for CaveRenderer {
+    fn default() -> Self {
+        Self {
+            meshes: HashMap::new(),
+            walls: HashMap::new(),
+            font: Font::new(64f32),
+            images: HashMap::new(),
+            colors: HashMap::new(),
+        }
+    }
+}
+impl CaveRenderer {
+    pub fn load(&mut self, core: &mut NGCore) {
+        self.colors.insert("floor",
+                           Ani::new(0.0,3.0,vec![Color::rgb_u8(150,77,0)])
+        );
+        self.colors.insert("walls",
+                           Ani::new(0.0,3.0,vec![Color::DARK_GRAY,Color::rgb_u8(100, 25,0),Color::DARK_GRAY])
+        );
+        self.colors.insert("warp",
+                           Ani::new(0.0,3.0,vec![Color::BLUE,Color::CYAN,Color::GREEN,Color::BLUE])
+        );
+        self.colors.insert("blood",
+                           Ani::new(0.0,3.0,vec![Color::new(0.25,0.0,0.0,1.0)])
+        );
+        self.colors.insert("exit",
+                           Ani::new(0.0,3.0,vec![Color::MAGENTA,Color::WHITE,Color::MAGENTA])
+        );
+        self.colors.insert("player",
+                           Ani::new(0.0,3.0,vec![Color::BLUE,Color::CYAN,Color::BLUE])
+        );
+        self.colors.insert("ghost",
+                           Ani::new(0.0,3.0,vec![Color::new(0.6,0.8,1.0,0.3),Color::new(0.8,0.9,1.0,0.45),Color::new(0.6,0.8,1.0,0.3)])
+        );
+        self.colors.insert("shop",
+                           Ani::new(0.0,2.0,vec![Color::DARK_CYAN,Color::CYAN,Color::DARK_CYAN])
+        );
+        self.colors.insert("boulder",
+                           Ani::new(0.0,3.0,vec![Color::GRAY,Color::DARK_GRAY,Color::GRAY])
+        );
+        self.colors.insert("plate",
+                           Ani::new(0.0,2.0,vec![Color::DIM_GRAY,Color::GRAY,Color::DIM_GRAY])
+        );
+        self.colors.insert("gate",
+                           Ani::new(0.0,1.0,vec![Color::DIM_GRAY,Color::rgb_u8(60,60,90),Color::DIM_GRAY])
+        );
+        self.colors.insert("checkpoint",
+                           Ani::new(0.0,3.0,vec![Color::DIM_GRAY,Color::GRAY,Color::DIM_GRAY])
+        );
+        self.colors.insert("checkpoint_active",
+                           Ani::new(0.0,1.5,vec![Color::SPRING_GREEN,Color::WHITE,Color::SPRING_GREEN])
+        );
+        let mut mb = MeshBuilder::default();
+        mb.solid(Color::BLACK);
+        mb.rect(vec2(8192,8192));
+        let darkness = mb.build();
+        mb.clear_meshes();
+        self.meshes.insert("darkness",darkness);
+        let base = core.create_image(8192,8192, true);
+        self.images.insert("base".to_owned(),base);
+        mb.solid(Color::WHITE);
+        mb.rounded_rect(vec2(TILE_WIDTH,TILE_WIDTH),4f32);
+        self.meshes.insert("player",mb.build());
+    }
+    /// Meshes for a run that just started on `data`.
+    pub fn level(&mut self, data: &str, sim: &Sim, soft_light: bool) {
+        self.meshes.insert("floor",floor_mesh(data));
+        self.walls(&sim.map);
+        let origin = sim.player.pos + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32;
+        self.meshes.insert("light",raycast_for_light(&origin,&sim.collision,light_reach(sim.player.fuel),soft_light));
+    }
+    /// Patches the meshes after a rock tile was dug out. The floor gets a tile added on top and
+    /// only the walls in the same chunk are rebuilt.
+    pub fn dug(&mut self, pos: (i32,i32), tiles: &HashMap<(i32,i32),TileType>) {
+        let mut mb = MeshBuilder::default();
+        mb.set_cursor(tile_pos(pos));
+        mb.solid(Color::rgb_u8(150,77,0));
+        mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+        let floor = self.meshes["floor"].add(&mb.build());
+        floor.buffer();
+        self.meshes.insert("floor",floor);
+        let chunk = chunk(pos);
+        self.walls.remove(&chunk);
+        if let Some(mesh) = wall_mesh(tiles,chunk) {
+            self.walls.insert(chunk,mesh);
+        }
+    }
+    /// Rebuilds every wall chunk from the tile map.
+    pub fn walls(&mut self, tiles: &HashMap<(i32,i32),TileType>) {
+        let mut chunks = tiles.keys().map(|p| chunk(*p)).collect::<Vec<_>>();
+        chunks.sort_unstable();
+        chunks.dedup();
+        self.walls = chunks.into_iter().filter_map(|c| Some((c,wall_mesh(tiles,c)?))).collect();
+    }
+    pub fn light(&mut self, origin: &Vec2, hits: &[Vec2], soft: bool) {
+        self.meshes.insert("light",light_mesh(origin,hits,soft));
+    }
+    /// Draws the cave, its tiles and everyone in it, then puts `g` back in screen space.
+    pub fn draw_world(&self, g: &mut ShapeGfx, view: &WorldView) {
+        let mut mb = MeshBuilder::default();
+        g.set_offset(-view.offset); // now g draws in world space instead of screen space.
+
+        g.draw_mesh(&self.meshes["darkness"],view.offset);
+        g.draw_mesh(&self.meshes["light"], view.offset);
+        g.render_image(&self.images["base"],true);
+
+        g.draw_mesh(&self.meshes["floor"], Vec2::ZERO);
+        view.sim.map.iter().for_each(|(p,t)|{
+            let pos = tile_pos(*p);
+            match t {
+                TileType::Shop => {
+                    mb.solid(self.colors["shop"].ani(view.time));
+                    mb.set_cursor(pos);
+                    mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+                }
+                TileType::Boulder => {
+                    mb.solid(self.colors["boulder"].ani(view.time));
+                    mb.set_cursor(pos);
+                    mb.rounded_rect(vec2(TILE_WIDTH,TILE_WIDTH),8f32);
+                }
+                TileType::Plate => {
+                    mb.solid(self.colors["plate"].ani(view.time));
+                    mb.set_cursor(pos + vec2(4,4));
+                    mb.rect(vec2(TILE_WIDTH - 8,TILE_WIDTH - 8));
+                }
+                TileType::Gate => {
+                    mb.solid(self.colors["gate"].ani(view.time));
+                    mb.set_cursor(pos);
+                    mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+                }
+                TileType::Checkpoint => {
+                    let active = view.sim.checkpoint.as_ref().is_some_and(|c| c.tile == *p);
+                    mb.solid(self.colors[if active {"checkpoint_active"} else {"checkpoint"}].ani(view.time));
+                    mb.set_cursor(pos + vec2(6,6));
+                    mb.rounded_rect(vec2(TILE_WIDTH - 12,TILE_WIDTH - 12),6f32);
+                }
+                TileType::Exit => {
+                    mb.solid(self.colors["exit"].ani(view.time));
+                    mb.set_cursor(pos);
+                    mb.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+                }
+                _ => {}
+            }
+        });
+        view.sim.entities.iter().for_each(|e| {
+            let sprite = &e.sprite;
+            let color = sprite.alert.as_ref().filter(|_| e.alerted()).unwrap_or(&sprite.color);
+            let size = vec2(TILE_WIDTH - sprite.inset * 2,TILE_WIDTH - sprite.inset * 2);
+            mb.solid(color.ani(view.time));
+            mb.set_cursor(e.ani.ani(view.sim_time) + vec2(sprite.inset,sprite.inset));
+            if sprite.corner > 0.0 { mb.rounded_rect(size,sprite.corner); } else { mb.rect(size); }
+        });
+        mb.solid(self.colors["player"].ani(view.time));
+        view.path.iter().for_each(|p| {
+            mb.set_cursor(tile_pos(*p) + vec2(TILE_WIDTH / 2 - 3,TILE_WIDTH / 2 - 3));
+            mb.rect(vec2(6,6));
+        });
+        g.draw_mesh(&mb.build(),Vec2::ZERO);
+        if let Some(ghost) = view.ghost {
+            g.set_tint(self.colors["ghost"].ani(view.time));
+            g.draw_mesh(&self.meshes["player"],ghost.player.ani.ani(view.sim_time));
+        }
+        g.set_tint(self.colors["player"].ani(view.time));
+        g.draw_mesh(&self.meshes["player"],view.sim.player.ani.ani(view.sim_time));
+        g.set_tint(self.colors["walls"].ani(view.time));
+        self.walls.values().for_each(|walls| g.draw_mesh(walls,Vec2::ZERO));
+        g.draw_image(&self.images["base"],Vec2::ZERO);
+        if view.debug {
+            let mut mb = MeshBuilder::default();
+            view.sim.dynamic_collision().iter().for_each(|l| l.visualize(&mut mb));
+            mb.solid(Color::YELLOW);
+            view.sim.light.iter().for_each(|hit| {
+                mb.set_cursor(*hit - vec2(2,2));
+                mb.rect(vec2(4,4));
+            });
+            g.draw_mesh(&mb.build(),Vec2::ZERO);
+        }
+        g.set_offset(Vec2::ZERO);
+    }
+}
+
+/// Builds the floor under every tile that isn't a wall, with blood and the ground around spikes stained.
+pub fn floor_mesh(data: &str) -> Mesh {
+    let mut floor_builder = MeshBuilder::default();
+
+    floor_builder.set_cursor(vec2(-1000,-1000));
+    floor_builder.solid(Color::BLACK);
+    floor_builder.rect(vec2(10000,10000));
+
+    let floor_color = Color::rgb_u8(150,77,0);
+
+    map_rows(data).enumerate().for_each(|(y,line)| {
+        line.chars().enumerate().for_each(|(x,c)| {
+            let color = match c {
+                'w' | 'r' => return,
+                'b' => Color::new(0.25,0.0,0.0,1.0),
+                's' => Color::ORANGE_RED,
+                '.' | '$' | 'o' | '_' | '|' | 'c' | 'e' | 'p' => floor_color,
+                c if EntityKind::from_glyph(c).is_some() => floor_color,
+                _ => return,
+            };
+            floor_builder.set_cursor(vec2(x as i32 * TILE_WIDTH, y as i32 * TILE_WIDTH));
+            floor_builder.solid(color);
+            floor_builder.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+        });
+    });
+
+    let floor = floor_builder.build();
+    floor.buffer();
+    floor
+}
+
+/// The wall chunk a tile is in.
+fn chunk(pos: (i32,i32)) -> (i32,i32) {
+    (pos.0.div_euclid(WALL_CHUNK),pos.1.div_euclid(WALL_CHUNK))
+}
+
+/// Builds the mesh for the walls in one chunk of the tile map, or nothing if there are none,
+/// so it can be redone when a wall in it is dug out.
+pub fn wall_mesh(tiles: &HashMap<(i32,i32),TileType>, chunk: (i32,i32)) -> Option<Mesh> {
+    let mut wall_builder = MeshBuilder::default();
+    wall_builder.solid(Color::GRAY);
+    let (x0,y0) = (chunk.0 * WALL_CHUNK,chunk.1 * WALL_CHUNK);
+    let mut empty = true;
+    (y0..y0 + WALL_CHUNK).flat_map(|y| (x0..x0 + WALL_CHUNK).map(move |x| (x,y))).for_each(|p| {
+        let color = match tiles.get(&p) {
+            Some(TileType::Wall) => Color::rgb(0.05,0.09,0.06),
+            Some(TileType::Rock) => Color::rgb(0.14,0.1,0.06),
+            _ => return,
+        };
+        empty = false;
+        wall_builder.set_cursor(tile_pos(p));
+        wall_builder.push();
+        wall_builder.solid(color);
+        wall_builder.rect(vec2(TILE_WIDTH,TILE_WIDTH));
+        wall_builder.pop();
+    });
+    if empty { return None }
+    let walls = wall_builder.build();
+    walls.buffer();
+    Some(walls)
+}
+
+pub fn raycast_for_light(origin: &Vec2, walls: &[LineSegment], reach: Vec2, soft: bool) -> Mesh {
+    light_mesh(origin,&visibility_polygon(origin,walls,reach),soft)
+}
+
+/// The lit area around `origin`. `soft` adds a blurred edge, which costs a stroke around the whole outline.
+pub fn light_mesh(origin: &Vec2, hits: &[Vec2], soft: bool) -> Mesh {
+    let mut mb = MeshBuilder::default();
+    mb.solid(Color::TRANSPARENT);
+    mb.set_thickness(TILE_WIDTH as f32 / 1.2);
+    let mut pb = PathBuilder;
+    for (i, hit) in hits.iter().enumerate() {
+        if i == 0 {
+            pb.move_to(*hit);
+            mb.set_cursor(*hit);
+            mb.rect(vec2(16,16) - vec2(8,8));
+        } else {
+            pb.line_to(*hit);
+            mb.set_cursor(*hit - vec2(8,8));
+            mb.rect(vec2(16,16));
+
+        }
+    }
+    pb.close_path(true);
+    let path = pb.build();
+
+    let mut light = fill_path_fan(origin,&path);
+    if soft {
+        mb.stroke_path(&path);
+        light = light.add(&mb.build());
+    }
+    //let mut light = triangle_fan(origin,hits);
+
+    light.solid(Color::TRANSPARENT);
+    light
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use crate::sim::Input;
+use crate::world::ShopItem;
+
+const MAGIC: &[u8; 4] = b"CAVR";
+const VERSION: u8 = 3;
+/// Player speed of replays from before it could be changed.
+const DEFAULT_MOVE_TICKS: u64 = 9;
+
+/// Every tick's input for one run, plus what's needed to start the same run again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub level_hash: u64,
+    pub seed: u64,
+    pub turn_based: bool,
+    /// Ticks between player moves the run was played with.
+    pub move_ticks: u64,
+    pub inputs: Vec<Input>,
+}
+impl Recording {
+    pub fn new(level_hash: u64, seed: u64, turn_based: bool, move_ticks: u64) -> Self {
+        Self { level_hash, seed, turn_based, move_ticks, inputs: vec![] }
+    }
+    /// Header followed by run-length encoded inputs, four bytes per run.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(u8::from(self.turn_based));
+        bytes.push(self.move_ticks.min(255) as u8);
+        bytes.extend(self.level_hash.to_le_bytes());
+        bytes.extend(self.seed.to_le_bytes());
+        let mut runs: Vec<(u16,u16)> = vec![];
+        self.inputs.iter().map(|i| encode(*i)).for_each(|bits| {
+            match runs.last_mut() {
+                Some((count,last)) if *last == bits && *count < u16::MAX => { *count += 1; }
+                _ => { runs.push((1,bits)); }
+            }
+        });
+        runs.iter().for_each(|(count,bits)| {
+            bytes.extend(count.to_le_bytes());
+            bytes.extend(bits.to_le_bytes());
+        });
+        bytes
+    }
+    /// Also reads older replays. Version 1 had no mode flag and one byte of input per run,
+    /// version 2 had no player speed.
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+        if bytes.len() < 5 || &bytes[0..4] != MAGIC { return Err(invalid("not a replay file")) }
+        let (turn_based,move_ticks,header,run_len) = match bytes[4] {
+            1 => (false,DEFAULT_MOVE_TICKS,5,3),
+            2 if bytes.len() > 5 => (bytes[5] != 0,DEFAULT_MOVE_TICKS,6,4),
+            3 if bytes.len() > 6 => (bytes[5] != 0,u64::from(bytes[6]).max(1),7,4),
+            _ => return Err(invalid("unsupported replay version")),
+        };
+        if bytes.len() < header + 16 { return Err(invalid("truncated replay")) }
+        let level_hash = u64::from_le_bytes(bytes[header..header + 8].try_into().unwrap());
+        let seed = u64::from_le_bytes(bytes[header + 8..header + 16].try_into().unwrap());
+        let runs = &bytes[header + 16..];
+        if !runs.len().is_multiple_of(run_len) { return Err(invalid("truncated replay")) }
+        let mut inputs = vec![];
+        for run in runs.chunks(run_len) {
+            let count = u16::from_le_bytes([run[0],run[1]]);
+            let bits = if run_len == 3 { u16::from(run[2]) } else { u16::from_le_bytes([run[2],run[3]]) };
+            let input = decode(bits).ok_or_else(|| invalid("bad input in replay"))?;
+            inputs.extend(std::iter::repeat_n(input,count as usize));
+        }
+        Ok(Self { level_hash, seed, turn_based, move_ticks, inputs })
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path,self.to_bytes())
+    }
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// FNV-1a of the level text. Unlike `DefaultHasher` it won't change between builds,
+/// so old replays can tell whether they still match the level.
+pub fn level_hash(data: &str) -> u64 {
+    data.bytes().fold(0xcbf2_9ce4_8422_2325, |hash,b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Packs an input into bits: four directions, leaving the shop, the item bought (0 for none), undo and redo.
+fn encode(input: Input) -> u16 {
+    let buy = input.buy.map_or(0, |item| ShopItem::ALL.iter().position(|i| *i == item).unwrap() as u16 + 1);
+    u16::from(input.up)
+        | u16::from(input.down) << 1
+        | u16::from(input.left) << 2
+        | u16::from(input.right) << 3
+        | u16::from(input.leave_shop) << 4
+        | buy << 5
+        | u16::from(input.undo) << 8
+        | u16::from(input.redo) << 9
+}
+
+fn decode(bits: u16) -> Option<Input> {
+    let buy = match bits >> 5 & 0b111 {
+        0 => None,
+        n => Some(*ShopItem::ALL.get(n as usize - 1)?),
+    };
+    Some(Input {
+        up: bits & 1 != 0,
+        down: bits & 1 << 1 != 0,
+        left: bits & 1 << 2 != 0,
+        right: bits & 1 << 3 != 0,
+        leave_shop: bits & 1 << 4 != 0,
+        buy,
+        undo: bits & 1 << 8 != 0,
+        redo: bits & 1 << 9 != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording() -> Recording {
+        let mut recording = Recording::new(level_hash("5\nwpw"),42,true,4);
+        let right = Input { right: true, ..Input::default() };
+        recording.inputs.extend(std::iter::repeat_n(right,300));
+        recording.inputs.push(Input { buy: Some(ShopItem::Pickaxe), leave_shop: true, ..Input::default() });
+        recording.inputs.push(Input { undo: true, up: true, ..Input::default() });
+        recording.inputs.push(Input::default());
+        recording
+    }
+
+    /// An old header: magic, version, then whatever that version had before the hash and seed.
+    fn header(version: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(version);
+        bytes.extend(7u64.to_le_bytes());
+        bytes.extend(8u64.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn round_trips() {
+        let recording = recording();
+        assert_eq!(Recording::from_bytes(&recording.to_bytes()).unwrap(),recording);
+        let path = std::env::temp_dir().join(format!("cave_escape_replay_test_{}.cavr",std::process::id()));
+        recording.save(&path).unwrap();
+        let loaded = Recording::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(),recording);
+    }
+
+    #[test]
+    fn level_hash_is_fnv1a() {
+        assert_eq!(level_hash(""),0xcbf2_9ce4_8422_2325);
+        assert_eq!(level_hash("a"),0xaf63_dc4c_8601_ec8c);
+        assert_ne!(level_hash("5\nwpw"),level_hash("5\nwp.w"));
+    }
+
+    #[test]
+    fn reads_version_1() {
+        let mut bytes = header(&[1]);
+        bytes.extend([2,0,0b1000]);
+        bytes.extend([1,0,0]);
+        let recording = Recording::from_bytes(&bytes).unwrap();
+        assert_eq!((recording.level_hash,recording.seed,recording.turn_based,recording.move_ticks),(7,8,false,DEFAULT_MOVE_TICKS));
+        let right = Input { right: true, ..Input::default() };
+        assert_eq!(recording.inputs,vec![right,right,Input::default()]);
+    }
+
+    #[test]
+    fn reads_version_2() {
+        let mut bytes = header(&[2,1]);
+        bytes.extend([1,0,0,0b10]);
+        let recording = Recording::from_bytes(&bytes).unwrap();
+        assert_eq!((recording.turn_based,recording.move_ticks),(true,DEFAULT_MOVE_TICKS));
+        assert_eq!(recording.inputs,vec![Input { redo: true, ..Input::default() }]);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let bytes = recording().to_bytes();
+        assert!(Recording::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Recording::from_bytes(&bytes[..20]).is_err());
+        assert!(Recording::from_bytes(b"CAV").is_err());
+        assert!(Recording::from_bytes(&header(&[9])).is_err());
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert!(Recording::from_bytes(&bad).is_err());
+    }
+}
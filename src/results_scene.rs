@@ -0,0 +1,169 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+use neo_granseal::mesh::{FillStyle, fill_path_fan, rect_filled};
+use neo_granseal::prelude::*;
+use neo_granseal::util::PathBuilder;
+use crate::cave::CaveEvent;
+use crate::input::{Action, Bindings};
+use crate::settings::Settings;
+use crate::speedrun::format_ticks;
+use crate::stats::RunStats;
+use crate::ui::{Ui, UiEvent, UiLabelStyle, UiThing};
+
+const STAR_RADIUS: f32 = 28.0;
+
+/// Pushed over the cave when the player reaches the exit, with the stats of the run.
+pub struct ResultsScene {
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    stats: RunStats,
+    /// Center of the first star on screen.
+    stars_at: Vec2,
+    ui: UiThing,
+}
+impl ResultsScene {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>) -> Self {
+        Self { settings, bindings, stats: RunStats::default(), stars_at: Vec2::ZERO, ui: UiThing::default() }
+    }
+    fn build_ui(&mut self, core: &NGCore) {
+        let scale = self.settings.borrow().ui_scale;
+        let stats = &self.stats;
+        let style = UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text: FillStyle::Solid(Color::ORANGE),
+            text_scale: 48f32 * scale,
+            ..Default::default()
+        };
+        let info_style = UiLabelStyle {
+            text: FillStyle::Solid(Color::WHITE),
+            hover_text: FillStyle::Solid(Color::WHITE),
+            text_scale: 32f32 * scale,
+            ..Default::default()
+        };
+        let mut children = vec![
+            Ui::Label {
+                name: "results_title".to_string(),
+                position: vec2(16,16),
+                text: format!("{} - Escaped!",stats.level_name),
+                children: vec![],
+                style: UiLabelStyle { text: FillStyle::Solid(Color::YELLOW), text_scale: 56f32 * scale, ..Default::default() },
+            }
+        ];
+        let mut y = 90f32 * scale;
+        let stars_at = vec2(32f32 + STAR_RADIUS * scale,y + STAR_RADIUS * scale);
+        if !stats.star_ticks.is_empty() {
+            y += (STAR_RADIUS * 2.0 + 16.0) * scale;
+        }
+        let lines = [
+            ("results_gold",format!("Gold: {}/{}",stats.gold,stats.gold_total)),
+            ("results_doors",format!("Doors opened: {}/{}",stats.doors,stats.doors_total)),
+            ("results_damage",format!("Damage taken: {}",stats.damage)),
+            ("results_steps",format!("Steps: {}",stats.steps)),
+            ("results_time",format!("Time: {}",format_ticks(stats.ticks))),
+        ];
+        for (name,text) in lines {
+            children.push(Ui::Label {
+                name: name.to_string(),
+                position: vec2(32f32,y),
+                text,
+                children: vec![],
+                style: info_style,
+            });
+            y += 40f32 * scale;
+        }
+        y += 16f32 * scale;
+        for (name,text) in [("results_continue","Continue"),("results_retry","Retry")] {
+            children.push(Ui::Label {
+                name: name.to_string(),
+                position: vec2(32f32,y),
+                text: text.to_string(),
+                children: vec![],
+                style,
+            });
+            y += 64f32 * scale;
+        }
+        let size = vec2(560f32 * scale,y + 16f32);
+        let position = (vec2(core.config.width,core.config.height) - size) / 2f32;
+        self.stars_at = stars_at + position;
+        let mut ui = UiThing::default();
+        ui.build(&Ui::Frame {
+            name: "results".to_string(),
+            position,
+            size,
+            children,
+            style: Default::default(),
+        });
+        self.ui = ui;
+    }
+    fn next(core: &mut NGCore) {
+        core.event(CaveEvent::Pop);
+        core.event(CaveEvent::NextLevel);
+    }
+}
+impl NeoGransealEventHandler for ResultsScene {
+    fn event(&mut self, core: &mut NGCore, event: Event) {
+        if self.ui.event(core,&event) {return}
+        match event {
+            Event::KeyEvent { state: KeyState::Pressed, key } if self.bindings.borrow().is(Action::Confirm,key) => {
+                Self::next(core);
+            }
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    match *msg {
+                        CaveEvent::RunFinished(stats) => {
+                            self.stats = stats;
+                            self.build_ui(core);
+                        }
+                        CaveEvent::Ui(UiEvent::MousePressed { id, .. }) => {
+                            match id.as_str() {
+                                "results_continue" => Self::next(core),
+                                "results_retry" => {
+                                    core.event(CaveEvent::Pop);
+                                    core.event(CaveEvent::RestartLevel);
+                                }
+                                _ => {}
+                            }
+                        }
+                        CaveEvent::SettingsChanged => { self.build_ui(core); }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Draw => {
+                let scale = self.settings.borrow().ui_scale;
+                let shade = rect_filled(vec2(0,0),vec2(core.config.width,core.config.height),FillStyle::Solid(Color::new(0.0,0.0,0.0,0.6)));
+                let mut g = ShapeGfx::new(core);
+                g.draw_mesh(&shade,vec2(0,0));
+
+                let mut mb = MeshBuilder::default();
+                self.ui.draw(&mut mb,&mut g);
+                g.draw_mesh(&mb.build(),Vec2::ZERO);
+
+                let earned = self.stats.stars();
+                for i in 0..self.stats.star_ticks.len() {
+                    let center = self.stars_at + vec2(i as f32 * STAR_RADIUS * 2.5 * scale,0);
+                    g.draw_mesh(&star(center,STAR_RADIUS * scale,if i < earned { Color::YELLOW } else { Color::DIM_GRAY }),Vec2::ZERO);
+                }
+            }
+            Event::Load => {
+                self.build_ui(core);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A five pointed star, filled in one color.
+fn star(center: Vec2, radius: f32, color: Color) -> Mesh {
+    let mut pb = PathBuilder;
+    for i in 0..10 {
+        let r = if i % 2 == 0 { radius } else { radius * 0.45 };
+        let point = center + angle_vec2(i as f32 * PI / 5.0 - PI / 2.0) * r;
+        if i == 0 { pb.move_to(point); } else { pb.line_to(point); }
+    }
+    pb.close_path(true);
+    let mut star = fill_path_fan(&center,&pb.build());
+    star.solid(color);
+    star
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use crate::entity::EntityKind;
+use crate::storage::data_dir;
+use crate::world::TileType;
+
+pub const SLOTS: usize = 3;
+
+/// Everything needed to pick a run back up: which level, where the player is and what they carry,
+/// the tile map with whatever they've dug, what's left of the gold, doors and other entities, and the run timer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveGame {
+    pub level: usize,
+    pub ticks: u64,
+    pub splits: Vec<u64>,
+    pub player: (i32,i32),
+    pub gold: i32,
+    pub health: i32,
+    pub fuel: i32,
+    pub keys: i32,
+    pub pickaxe: bool,
+    pub tiles: HashMap<(i32,i32),TileType>,
+    /// Where each entity still in the level is. Doors get their price back from the level data.
+    pub entities: Vec<((i32,i32),EntityKind)>,
+}
+impl SaveGame {
+    /// A few `name value` lines, then `map` and the tile map as rows of glyphs, with entities drawn over their tiles.
+    pub fn to_text(&self) -> String {
+        let splits = self.splits.iter().map(u64::to_string).collect::<Vec<_>>().join(" ");
+        let mut text = format!(
+            "level {}\nticks {}\nsplits {}\nplayer {} {}\ngold {}\nhealth {}\nfuel {}\nkeys {}\npickaxe {}\nmap\n",
+            self.level, self.ticks, splits, self.player.0, self.player.1,
+            self.gold, self.health, self.fuel, self.keys, u8::from(self.pickaxe),
+        );
+        let width = self.tiles.keys().map(|p| p.0).max().unwrap_or(-1) + 1;
+        let height = self.tiles.keys().map(|p| p.1).max().unwrap_or(-1) + 1;
+        for y in 0..height {
+            let row = (0..width).map(|x| {
+                let entity = self.entities.iter().find(|(pos,_)| *pos == (x,y)).map(|(_,kind)| kind.glyph());
+                entity.or_else(|| self.tiles.get(&(x,y)).map(|t| t.glyph())).unwrap_or(' ')
+            }).collect::<String>();
+            text.push_str(row.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+    pub fn from_text(text: &str) -> std::io::Result<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+        let mut save = SaveGame::default();
+        let mut lines = text.lines();
+        for line in lines.by_ref() {
+            if line == "map" { break }
+            let (name,value) = line.split_once(' ').unwrap_or((line,""));
+            let number = || value.trim().parse::<i32>().map_err(|_| invalid(line));
+            match name {
+                "level" => { save.level = value.trim().parse().map_err(|_| invalid(line))?; }
+                "ticks" => { save.ticks = value.trim().parse().map_err(|_| invalid(line))?; }
+                "splits" => { save.splits = value.split_whitespace().filter_map(|s| s.parse().ok()).collect(); }
+                "player" => {
+                    let (x,y) = value.split_once(' ').ok_or_else(|| invalid(line))?;
+                    save.player = (x.parse().map_err(|_| invalid(line))?,y.parse().map_err(|_| invalid(line))?);
+                }
+                "gold" => { save.gold = number()?; }
+                "health" => { save.health = number()?; }
+                "fuel" => { save.fuel = number()?; }
+                "keys" => { save.keys = number()?; }
+                "pickaxe" => { save.pickaxe = number()? != 0; }
+                _ => return Err(invalid(line)),
+            }
+        }
+        lines.enumerate().for_each(|(y,row)| {
+            row.chars().enumerate().for_each(|(x,c)| {
+                let pos = (x as i32,y as i32);
+                if let Some(tile) = TileType::from_glyph(c) {
+                    save.tiles.insert(pos,tile);
+                } else if let Some(kind) = EntityKind::from_glyph(c) {
+                    save.tiles.insert(pos,TileType::Floor);
+                    save.entities.push((pos,kind));
+                }
+            });
+        });
+        Ok(save)
+    }
+    pub fn save(&self, slot: usize) -> std::io::Result<()> {
+        fs::create_dir_all(slot_path(slot).parent().unwrap())?;
+        fs::write(slot_path(slot),self.to_text())
+    }
+    pub fn load(slot: usize) -> std::io::Result<Self> {
+        Self::from_text(&fs::read_to_string(slot_path(slot))?)
+    }
+}
+
+pub fn slot_path(slot: usize) -> PathBuf {
+    data_dir().join("saves").join(format!("slot{}.txt",slot + 1))
+}
+
+fn modified(slot: usize) -> Option<SystemTime> {
+    fs::metadata(slot_path(slot)).and_then(|m| m.modified()).ok()
+}
+
+/// The most recently saved slot, what Continue picks up.
+pub fn latest_slot() -> Option<usize> {
+    (0..SLOTS).filter_map(|slot| Some((slot,modified(slot)?))).max_by_key(|(_,time)| *time).map(|(slot,_)| slot)
+}
+
+/// Slot for a new game: the first empty one, or the oldest if they're all taken.
+pub fn free_slot() -> usize {
+    (0..SLOTS).find(|slot| modified(*slot).is_none())
+        .or_else(|| (0..SLOTS).min_by_key(|slot| modified(*slot)))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::parse_map;
+
+    #[test]
+    fn reads_what_it_writes() {
+        let level = parse_map("5\nwwwwww\nwp.gkw\nwr_|dw\nwwwwww").unwrap();
+        let save = SaveGame {
+            level: 2,
+            ticks: 1234,
+            splits: vec![400,900],
+            player: (1,1),
+            gold: 7,
+            health: 3,
+            fuel: 250,
+            keys: 1,
+            pickaxe: true,
+            entities: level.entities.iter().map(|e| (e.tile,e.kind)).collect(),
+            tiles: level.tiles,
+        };
+        let text = save.to_text();
+        assert!(text.ends_with("map\nwwwwww\nw..gkw\nwr_|dw\nwwwwww\n"),"entities are drawn over their tiles");
+        assert_eq!(SaveGame::from_text(&text).unwrap(),save);
+    }
+
+    #[test]
+    fn reads_the_torch_fuel() {
+        let save = SaveGame::from_text("level 0\nfuel 120\nmap\n.").unwrap();
+        assert_eq!(save.fuel,120);
+        assert!(SaveGame::from_text("fuel lots\nmap\n").is_err());
+    }
+}
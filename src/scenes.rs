@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use neo_granseal::mesh::{FillStyle, fill_path_fan, rect_filled};
+use neo_granseal::prelude::*;
+use neo_granseal::util::PathBuilder;
+use crate::cave::CaveEvent;
+
+/// Default length of a transition in seconds.
+pub const TRANSITION_TIME: f32 = 0.6;
+
+/// Names for the scenes, used to switch between them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SceneId {
+    Title,
+    Cave,
+    Controls,
+    Pause,
+    Options,
+    LevelSelect,
+    Results,
+}
+impl SceneId {
+    pub fn name(self) -> &'static str {
+        match self {
+            SceneId::Title => "Title",
+            SceneId::Cave => "Cave",
+            SceneId::Controls => "Controls",
+            SceneId::Pause => "Paused",
+            SceneId::Options => "Options",
+            SceneId::LevelSelect => "Level Select",
+            SceneId::Results => "Results",
+        }
+    }
+    /// The scenes that can be started on from the command line. The rest need a scene under them.
+    pub fn from_arg(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(SceneId::Title),
+            "cave" => Some(SceneId::Cave),
+            "levels" => Some(SceneId::LevelSelect),
+            "options" => Some(SceneId::Options),
+            "controls" => Some(SceneId::Controls),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransitionKind {
+    /// Fade to black and back.
+    Fade,
+    /// A circle closing in on a point on screen, then opening again.
+    Iris(Vec2),
+    /// A black panel sliding across from the left, then off to the right.
+    Slide,
+}
+
+/// How the next scene change looks. Sent as [`CaveEvent::Transition`] just before the change.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    /// Seconds for the whole thing, half covering the old scene and half uncovering the new one.
+    pub duration: f32,
+}
+impl Transition {
+    pub fn fade(duration: f32) -> Self {
+        Self { kind: TransitionKind::Fade, duration }
+    }
+    pub fn iris(center: Vec2, duration: f32) -> Self {
+        Self { kind: TransitionKind::Iris(center), duration }
+    }
+    pub fn slide(duration: f32) -> Self {
+        Self { kind: TransitionKind::Slide, duration }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StackChange {
+    Push(SceneId),
+    Pop,
+    Replace(SceneId),
+    Switch(SceneId),
+}
+
+/// A transition in progress. The stack changes are held back until the screen is fully covered.
+struct Running {
+    transition: Transition,
+    start: f32,
+    /// How much of the screen is covered, 0 to 1 and back.
+    cover: Ani<f32>,
+    changes: Vec<StackChange>,
+}
+
+/// Owns every scene and a stack of the ones being shown. The top scene gets input and updates,
+/// the ones under it are still drawn, so a scene can be pushed as an overlay.
+/// Scenes live as long as the handler, leaving one and coming back finds it as it was.
+pub struct SceneHandler {
+    scenes: HashMap<SceneId,Box<dyn NeoGransealEventHandler>>,
+    /// Bottom to top.
+    stack: Vec<SceneId>,
+    /// Used for the next stack change.
+    next_transition: Option<Transition>,
+    running: Option<Running>,
+}
+impl SceneHandler {
+    /// Starts with these scenes on the stack, bottom first.
+    pub fn new(stack: Vec<SceneId>) -> Self {
+        Self { scenes: HashMap::new(), stack, next_transition: None, running: None }
+    }
+    pub fn scene(mut self, id: SceneId, scene: impl NeoGransealEventHandler + 'static) -> Self {
+        self.scenes.insert(id,Box::new(scene));
+        self
+    }
+    fn send(&mut self, id: SceneId, core: &mut NGCore, event: Event) {
+        if let Some(scene) = self.scenes.get_mut(&id) {
+            scene.event(core,event);
+        }
+    }
+    /// Tells every scene whether a transition is playing.
+    fn announce_transition(&mut self, core: &mut NGCore, running: bool) {
+        self.scenes.values_mut().for_each(|scene| scene.event(core,Event::Custom(Box::new(CaveEvent::TransitionRunning(running)))));
+    }
+    fn top(&self) -> Option<SceneId> {
+        self.stack.last().copied()
+    }
+    fn change(&mut self, change: StackChange, core: &mut NGCore) {
+        if let Some(running) = &mut self.running {
+            // Rides along with the transition already on screen, so one sent for this change is used up too.
+            running.changes.push(change);
+            self.next_transition = None;
+        } else if let Some(transition) = self.next_transition.take() {
+            let start = core.timer.elapsed().as_secs_f32();
+            let mut cover = Ani::new(start,transition.duration,vec![0.0,1.0,0.0]);
+            cover.repeat = false;
+            self.running = Some(Running { transition, start, cover, changes: vec![change] });
+            self.announce_transition(core,true);
+        } else {
+            self.apply(change,core);
+        }
+    }
+    fn apply(&mut self, change: StackChange, core: &mut NGCore) {
+        match change {
+            StackChange::Push(id) => { self.stack.push(id); }
+            StackChange::Pop => {
+                // Never pop the last scene, there'd be nothing left to show.
+                if self.stack.len() > 1 { self.stack.pop(); }
+            }
+            StackChange::Replace(id) => {
+                self.stack.pop();
+                self.stack.push(id);
+            }
+            StackChange::Switch(id) => {
+                self.stack.clear();
+                self.stack.push(id);
+            }
+        }
+        if let Some(top) = self.top() {
+            self.send(top,core,Event::Custom(Box::new(CaveEvent::Shown)));
+        }
+    }
+    /// Switches scenes once the screen is covered, and finishes the transition once it's uncovered.
+    fn update_transition(&mut self, core: &mut NGCore) {
+        let Some(running) = &mut self.running else { return };
+        let elapsed = core.timer.elapsed().as_secs_f32() - running.start;
+        let changes = if elapsed >= running.transition.duration / 2.0 { std::mem::take(&mut running.changes) } else { vec![] };
+        let finished = elapsed >= running.transition.duration;
+        if finished {
+            self.running = None;
+        }
+        changes.into_iter().for_each(|change| self.apply(change,core));
+        if finished {
+            self.announce_transition(core,false);
+        }
+    }
+    fn draw_transition(&self, core: &mut NGCore) {
+        let Some(running) = &self.running else { return };
+        let cover = running.cover.ani(core.timer.elapsed().as_secs_f32()).clamp(0.0,1.0);
+        let size = vec2(core.config.width,core.config.height);
+        let mesh = match running.transition.kind {
+            TransitionKind::Fade => rect_filled(Vec2::ZERO,size,FillStyle::Solid(Color::new(0.0,0.0,0.0,cover))),
+            TransitionKind::Slide => {
+                rect_filled(vec2(size.x * (cover - 1.0),0.0),size,FillStyle::Solid(Color::BLACK))
+            }
+            TransitionKind::Iris(center) => {
+                // A ring from the edge of the hole out past the corners of the screen, one quad at a time.
+                let outer = size.x + size.y;
+                let inner = outer * (1.0 - cover);
+                let point = |radius: f32, i: usize| {
+                    let angle = i as f32 / 48.0 * std::f32::consts::TAU;
+                    center + vec2(angle.cos() * radius,angle.sin() * radius)
+                };
+                let mut ring = (0..48).map(|i| {
+                    let mut pb = PathBuilder;
+                    pb.move_to(point(inner,i));
+                    pb.line_to(point(outer,i));
+                    pb.line_to(point(outer,i + 1));
+                    pb.line_to(point(inner,i + 1));
+                    pb.close_path(true);
+                    fill_path_fan(&point(inner,i),&pb.build())
+                }).reduce(|ring,quad| ring.add(&quad)).unwrap();
+                ring.solid(Color::BLACK);
+                ring
+            }
+        };
+        let mut g = ShapeGfx::new(core);
+        g.draw_mesh(&mesh,Vec2::ZERO);
+    }
+}
+
+impl NeoGransealEventHandler for SceneHandler {
+    fn event(&mut self, core: &mut NGCore, event: Event) {
+        match event {
+            Event::Load => {
+                self.scenes.values_mut().for_each(|scene| scene.event(core,Event::Load));
+            }
+            Event::Custom(msg) => {
+                let msg = *msg.downcast::<CaveEvent>().unwrap_or(Box::new(CaveEvent::Error));
+                match msg {
+                    CaveEvent::Push(id) => { self.change(StackChange::Push(id),core); }
+                    CaveEvent::Pop => { self.change(StackChange::Pop,core); }
+                    CaveEvent::Replace(id) => { self.change(StackChange::Replace(id),core); }
+                    CaveEvent::Switch(id) => { self.change(StackChange::Switch(id),core); }
+                    CaveEvent::Shown | CaveEvent::TransitionRunning(_) => {}
+                    CaveEvent::Transition(transition) => { self.next_transition = Some(transition); }
+                    CaveEvent::Error => {println!("Received a strange event, could not unwrap it."); }
+                    CaveEvent::NewGame | CaveEvent::ContinueGame | CaveEvent::RestartLevel | CaveEvent::PlayLevel(_) | CaveEvent::NextLevel | CaveEvent::QuitToTitle => {
+                        self.send(SceneId::Cave,core,Event::Custom(Box::new(msg)));
+                    }
+                    CaveEvent::RunFinished(_) => {
+                        self.send(SceneId::Results,core,Event::Custom(Box::new(msg)));
+                    }
+                    CaveEvent::SettingsChanged => {
+                        self.scenes.values_mut().for_each(|scene| scene.event(core,Event::Custom(Box::new(CaveEvent::SettingsChanged))));
+                    }
+                    CaveEvent::Ui(ui) => {
+                        if let Some(top) = self.top() {
+                            self.send(top,core,Event::Custom(Box::new(CaveEvent::Ui(ui))));
+                        }
+                    }
+                }
+            }
+            Event::Draw => {
+                for id in self.stack.clone() {
+                    self.send(id,core,Event::Draw);
+                }
+                self.draw_transition(core);
+            }
+            Event::Update(_) =>  {
+                self.update_transition(core);
+                if let Some(top) = self.top() {
+                    core.set_title(format!("{}: {}",top.name(),core.state.fps));
+                    self.send(top,core,event);
+                }
+            }
+            // No input while a transition is playing.
+            Event::KeyEvent { .. } | Event::MousePressed { .. } | Event::MouseMoved(..) | Event::MouseWheel(..) if self.running.is_some() => {}
+            _ => {
+                if let Some(top) = self.top() {
+                    self.send(top,core,event);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::storage::config_dir;
+use crate::TILE_WIDTH;
+
+/// Window sizes the options scene cycles through.
+pub const WINDOW_SIZES: [(i32,i32); 4] = [(30 * TILE_WIDTH,30 * TILE_WIDTH),(1120,840),(1280,960),(1600,900)];
+/// Ticks between player moves, slow to fast.
+pub const MOVE_SPEEDS: [(u64,&str); 3] = [(12,"Slow"),(9,"Normal"),(6,"Fast")];
+pub const UI_SCALES: [f32; 4] = [0.75,1.0,1.25,1.5];
+
+/// Everything on the options scene. Saved as `name = value` lines, anything missing or unreadable keeps its default.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Settings {
+    pub width: i32,
+    pub height: i32,
+    pub vsync: bool,
+    pub fullscreen: bool,
+    /// Ticks between player moves, see [`MOVE_SPEEDS`].
+    pub move_ticks: u64,
+    /// Draw the soft edge around the light. Only changes how the light looks, never what it touches.
+    pub soft_light: bool,
+    pub ui_scale: f32,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            width: 30 * TILE_WIDTH,
+            height: 30 * TILE_WIDTH,
+            vsync: false,
+            fullscreen: false,
+            move_ticks: 9,
+            soft_light: true,
+            ui_scale: 1.0,
+        }
+    }
+}
+impl Settings {
+    pub fn load() -> Self {
+        fs::read_to_string(settings_path()).map(|data| Self::from_text(&data)).unwrap_or_default()
+    }
+    /// Reads the settings file. Window sizes and ui scales that aren't positive keep their defaults.
+    pub fn from_text(data: &str) -> Self {
+        let mut settings = Self::default();
+        data.lines().filter_map(|line| line.split_once('=')).for_each(|(name,value)| {
+            let value = value.trim();
+            match name.trim() {
+                "width" => { settings.width = value.parse().ok().filter(|w| *w > 0).unwrap_or(settings.width); }
+                "height" => { settings.height = value.parse().ok().filter(|h| *h > 0).unwrap_or(settings.height); }
+                "vsync" => { settings.vsync = value.parse().unwrap_or(settings.vsync); }
+                "fullscreen" => { settings.fullscreen = value.parse().unwrap_or(settings.fullscreen); }
+                "move_ticks" => { settings.move_ticks = value.parse().unwrap_or(settings.move_ticks).max(1); }
+                "soft_light" => { settings.soft_light = value.parse().unwrap_or(settings.soft_light); }
+                "ui_scale" => { settings.ui_scale = value.parse().ok().filter(|s: &f32| *s > 0.0).unwrap_or(settings.ui_scale); }
+                _ => {}
+            }
+        });
+        settings
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        let data = format!(
+            "width = {}\nheight = {}\nvsync = {}\nfullscreen = {}\nmove_ticks = {}\nsoft_light = {}\nui_scale = {}\n",
+            self.width, self.height, self.vsync, self.fullscreen, self.move_ticks, self.soft_light, self.ui_scale,
+        );
+        fs::create_dir_all(config_dir())?;
+        fs::write(settings_path(),data)
+    }
+    pub fn move_speed_name(&self) -> &'static str {
+        MOVE_SPEEDS.iter().find(|(ticks,_)| *ticks == self.move_ticks).map_or("Custom",|(_,name)| name)
+    }
+}
+
+/// The entry after `current` in `options`, wrapping around. Starts from the first if `current` isn't one of them.
+pub fn cycle<T: PartialEq + Copy>(options: &[T], current: T) -> T {
+    let i = options.iter().position(|o| *o == current).map_or(0,|i| (i + 1) % options.len());
+    options[i]
+}
+
+fn settings_path() -> PathBuf {
+    config_dir().join("settings.txt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_what_it_saves() {
+        let settings = Settings::from_text("width = 1280\nheight = 960\nvsync = true\nmove_ticks = 6\nui_scale = 1.5");
+        assert_eq!(settings,Settings { width: 1280, height: 960, vsync: true, move_ticks: 6, ui_scale: 1.5, ..Settings::default() });
+    }
+
+    #[test]
+    fn sizes_and_scales_must_be_positive() {
+        let settings = Settings::from_text("width = 0\nheight = -720\nui_scale = 0\nmove_ticks = 0");
+        let default = Settings::default();
+        assert_eq!((settings.width,settings.height,settings.ui_scale),(default.width,default.height,default.ui_scale));
+        assert_eq!(settings.move_ticks,1);
+        assert_eq!(Settings::from_text("ui_scale = -1").ui_scale,default.ui_scale);
+    }
+}
@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use neo_granseal::prelude::*;
+use neo_granseal::util::LineSegment;
+use crate::enemy::Step;
+use crate::entity::{Behaviour, Entities, EntityKind};
+use crate::history::{entity_changes, tile_changes, History, Turn};
+use crate::moves::{Direction, MoveQueue};
+use crate::TILE_WIDTH;
+use crate::world::{FULL_FUEL, in_light, light_reach, MapInfo, patch_collision, Player, SCREEN, ShopItem, TileType, tile_pos, visibility_polygon, wall_collision};
+
+pub const TICKS_PER_SECOND: u64 = 60;
+/// Length of one simulation tick in seconds.
+pub const DT: f32 = 1.0 / TICKS_PER_SECOND as f32;
+
+/// Everything the player asked for during one tick.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Input {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub buy: Option<ShopItem>,
+    pub leave_shop: bool,
+    pub undo: bool,
+    pub redo: bool,
+}
+impl Input {
+    pub fn moving(&self) -> bool {
+        self.up || self.down || self.left || self.right
+    }
+    /// Holds down the key for `dir`, as if the player had pressed it.
+    pub fn hold(&mut self, dir: Direction) {
+        match dir {
+            Direction::Up => self.up = true,
+            Direction::Down => self.down = true,
+            Direction::Left => self.left = true,
+            Direction::Right => self.right = true,
+        }
+    }
+}
+
+/// Things that happened during a tick that the scene needs to react to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SimEvent {
+    ShopOpened,
+    ShopClosed,
+    Purchased(ShopItem),
+    Dug((i32,i32)),
+    /// The player stepped onto another tile.
+    Moved,
+    /// The player took a pickup, like gold or a key.
+    PickedUp(EntityKind),
+    DoorOpened((i32,i32)),
+    /// The player lost a point of health to spikes or an enemy.
+    Hurt,
+    /// The player reached a checkpoint and it now holds their progress.
+    Checkpoint((i32,i32)),
+    /// The player died and was put back at the last checkpoint.
+    Respawned,
+    /// The player died without reaching a checkpoint, the level has to start over.
+    Died,
+    /// A turn was taken back or played again, tiles may have changed under the meshes.
+    Undone,
+    Redone,
+    /// The player reached the exit, the run is over.
+    Escaped,
+}
+
+/// Xorshift64, small and the same on every platform so seeded runs replay exactly.
+/// Nothing in the cave is random yet. Runs and replays keep their seed for when something is.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero.
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+/// What the level looked like when the player last touched a checkpoint.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub tile: (i32,i32),
+    pub map: HashMap<(i32,i32),TileType>,
+    pub player: Player,
+    pub entities: Entities,
+}
+
+/// The cave's game state, stepped at a fixed rate. Nothing in here looks at the clock,
+/// the same inputs from the same starting state always give the same result.
+#[derive(Clone)]
+pub struct Sim {
+    pub tick: u64,
+    pub rng: Rng,
+    pub map: HashMap<(i32,i32),TileType>,
+    /// Merged wall edges, see [`wall_collision`].
+    pub collision: Vec<LineSegment>,
+    pub player: Player,
+    pub moves: MoveQueue,
+    pub prices: Vec<(ShopItem,i32)>,
+    pub plates: Vec<((i32,i32),(i32,i32))>,
+    /// Gold, doors, traps, enemies and everything else that isn't part of the tile map.
+    pub entities: Entities,
+    pub shopping: bool,
+    pub escaped: bool,
+    pub checkpoint: Option<Checkpoint>,
+    /// Enemies only move when the player does, and moves can be undone.
+    pub turn_based: bool,
+    pub history: History,
+    pub light_origin: Vec2,
+    /// The player's visibility polygon as of the last tick.
+    pub light: Vec<Vec2>,
+    /// Tiles changed this tick, each with what it was before, for the turn history.
+    pub edits: Vec<((i32,i32),TileType)>,
+}
+impl Default for Sim {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            rng: Rng::new(0),
+            map: HashMap::new(),
+            collision: vec![],
+            player: Player::new(),
+            moves: MoveQueue::default(),
+            prices: vec![],
+            plates: vec![],
+            entities: Entities::default(),
+            shopping: false,
+            escaped: false,
+            checkpoint: None,
+            turn_based: false,
+            history: History::default(),
+            light_origin: Vec2::ZERO,
+            light: vec![],
+            edits: vec![],
+        }
+    }
+}
+impl Sim {
+    /// A fresh run on a parsed level. The cave and the headless runner both start runs here.
+    pub fn new(map: MapInfo, seed: u64, move_ticks: u64, turn_based: bool) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            map: map.tiles,
+            collision: map.collision,
+            player: Player { pos: map.player_start, health: 5, freeze_ticks: move_ticks, ..Player::new() },
+            prices: map.prices,
+            plates: map.plates,
+            entities: map.entities,
+            turn_based,
+            ..Self::default()
+        }
+    }
+    /// Whether the player can walk onto `pos` without being stopped, it's open ground with nothing solid on it.
+    pub fn walkable(&self, pos: (i32,i32)) -> bool {
+        open_ground(&self.map,pos) && !self.entities.blocks(pos)
+    }
+    /// Simulation time in seconds, used to start tweens.
+    pub fn time(&self) -> f32 {
+        self.tick as f32 * DT
+    }
+    pub fn step(&mut self, input: Input) -> Vec<SimEvent> {
+        let mut events = vec![];
+        self.edits.clear();
+        self.moves.update(&input);
+        if self.shopping {
+            if let Some(item) = input.buy {
+                if self.buy(item) {
+                    events.push(SimEvent::Purchased(item));
+                }
+            }
+            if input.leave_shop {
+                self.shopping = false;
+                self.moves.clear();
+                events.push(SimEvent::ShopClosed);
+            }
+        } else if self.turn_based && !self.escaped && (input.undo || input.redo) {
+            let turn = if input.undo { self.history.undo() } else { self.history.redo() };
+            if let Some(turn) = turn {
+                self.replay_turn(&turn,input.undo);
+                events.push(if input.undo { SimEvent::Undone } else { SimEvent::Redone });
+            }
+        } else if self.turn_based && !self.escaped {
+            self.take_turn(&mut events);
+        } else if !self.escaped && self.tick >= self.player.next_move {
+            if let Some(dir) = self.moves.next() {
+                self.move_player(dir,&mut events);
+            }
+        }
+
+        let collision = self.dynamic_collision();
+        self.light_origin = self.player.ani.ani(self.time()) + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32;
+        self.light = visibility_polygon(&self.light_origin,&collision,light_reach(self.player.fuel));
+        if !self.turn_based {
+            self.update_enemies(&collision,&mut events);
+        }
+        if self.player.health <= 0 && !self.escaped {
+            events.push(self.respawn());
+        }
+
+        self.tick += 1;
+        events
+    }
+    /// Moves the player, and if that changed anything, lets the enemies take their step and
+    /// remembers what changed so it can be undone.
+    fn take_turn(&mut self, events: &mut Vec<SimEvent>) {
+        if self.tick < self.player.next_move { return }
+        let Some(dir) = self.moves.next() else { return };
+        let player = self.player.clone();
+        let entities = self.entities.clone();
+        // Kept out of the way, so it's only copied into the history if the move reaches a new one.
+        let before = self.checkpoint.take();
+        self.move_player(dir,events);
+        let checkpoint = match &self.checkpoint {
+            Some(reached) => Some((before,reached.clone())),
+            None => {
+                self.checkpoint = before;
+                None
+            }
+        };
+        let tiles = tile_changes(&self.edits,&self.map);
+        let p = &self.player;
+        let acted = !tiles.is_empty() || p.pos != player.pos || p.health != player.health || p.gold != player.gold
+            || self.entities.len() != entities.len();
+        if !acted { return }
+
+        // Enemies get exactly one step per turn, whatever their own pace.
+        let tick = self.tick;
+        self.entities.iter_mut().for_each(|e| if let Behaviour::Enemy(enemy) = &mut e.behaviour { enemy.next_move = tick; });
+        let collision = self.dynamic_collision();
+        self.update_enemies(&collision,events);
+        self.history.push(Turn {
+            tiles,
+            player: (player,self.player.clone()),
+            entities: entity_changes(&entities,&self.entities),
+            checkpoint,
+        });
+    }
+    /// Puts the tiles, player, entities and checkpoint back to how they were before (`undo`) or after a turn.
+    fn replay_turn(&mut self, turn: &Turn, undo: bool) {
+        let time = self.time();
+        turn.tiles.iter().for_each(|(pos,old,new)| {
+            self.map.insert(*pos,if undo { *old } else { *new });
+            // Dug out or filled back in.
+            if *old == TileType::Rock || *new == TileType::Rock {
+                patch_collision(&mut self.collision,&self.map,*pos);
+            }
+        });
+        let player = if undo { &turn.player.0 } else { &turn.player.1 };
+        if let Some((before,reached)) = &turn.checkpoint {
+            self.checkpoint = if undo { before.clone() } else { Some(reached.clone()) };
+        }
+        turn.entities.iter().for_each(|(id,before,after)| {
+            self.entities.put(*id,if undo { before.clone() } else { after.clone() });
+        });
+        self.player = Player {
+            ani: Ani::new(time,1.0,vec![player.pos]),
+            next_move: self.tick + self.player.freeze_ticks,
+            ..player.clone()
+        };
+        self.entities.iter_mut().for_each(|e| e.ani = Ani::new(time,1.0,vec![e.pos()]));
+    }
+    /// Tries to step one tile in `dir`, reacting to whatever is there.
+    fn move_player(&mut self, dir: Direction, events: &mut Vec<SimEvent>) {
+        let time = self.time();
+        let tick = self.tick;
+        let (dx,dy) = dir.offset();
+        let t_pos = (self.player.tile().0 + dx,self.player.tile().1 + dy);
+        // Whatever's on the tile gets run into first, and may keep the player out.
+        let blocked = !self.entities.touch(t_pos,&mut self.player,events);
+        let player = &mut self.player;
+        let from = player.pos;
+        let mut pushed = false;
+        let mut checkpoint = false;
+        let new_pos = player.pos + vec2(dx * TILE_WIDTH,dy * TILE_WIDTH);
+        player.next_move = tick + player.freeze_ticks;
+        let duration = player.freeze_ticks as f32 * DT;
+        let default = &mut TileType::Wall;
+        let t_type = if blocked {
+            default
+        } else {
+            self.map.get_mut(&t_pos).unwrap_or(default)
+        };
+        match t_type {
+            TileType::Floor | TileType::Plate | TileType::OpenGate => {
+                player.ani = Ani::new(time,duration,vec![player.pos,new_pos]);
+                player.ani.repeat = false;
+                player.pos = new_pos;
+            }
+            TileType::Checkpoint => {
+                player.ani = Ani::new(time,duration,vec![player.pos,new_pos]);
+                player.ani.repeat = false;
+                player.pos = new_pos;
+                checkpoint = true;
+            }
+            TileType::Wall | TileType::Gate => {}
+            TileType::Boulder => {
+                let beyond = (t_pos.0 + dx, t_pos.1 + dy);
+                // Only onto open ground with nothing on it.
+                if matches!(self.map.get(&beyond), Some(TileType::Floor | TileType::Plate)) && self.entities.at(beyond).is_none() {
+                    let under = if self.plates.iter().any(|(plate,_)| *plate == t_pos) { TileType::Plate } else { TileType::Floor };
+                    set_tile(&mut self.map,&mut self.edits,t_pos,under);
+                    set_tile(&mut self.map,&mut self.edits,beyond,TileType::Boulder);
+                    player.ani = Ani::new(time,duration,vec![player.pos,new_pos]);
+                    player.ani.repeat = false;
+                    player.pos = new_pos;
+                    pushed = true;
+                }
+            }
+            TileType::Rock => {
+                if player.pickaxe {
+                    self.dig(t_pos);
+                    events.push(SimEvent::Dug(t_pos));
+                }
+            }
+            TileType::Warp => {}
+            TileType::Exit => {
+                player.ani = Ani::new(time,duration,vec![player.pos,new_pos]);
+                player.ani.repeat = false;
+                player.pos = new_pos;
+                self.escaped = true;
+            }
+            TileType::Shop => {
+                self.shopping = true;
+                events.push(SimEvent::ShopOpened);
+            }
+        }
+        if self.player.pos != from {
+            self.player.fuel = (self.player.fuel - 1).max(0);
+            events.push(SimEvent::Moved);
+        }
+        // After the step, so it's counted before the run ends.
+        if self.escaped {
+            events.push(SimEvent::Escaped);
+        }
+        if pushed {
+            self.update_gates();
+        }
+        if checkpoint {
+            self.checkpoint = Some(Checkpoint {
+                tile: t_pos,
+                map: self.map.clone(),
+                player: self.player.clone(),
+                entities: self.entities.clone(),
+            });
+            events.push(SimEvent::Checkpoint(t_pos));
+        }
+    }
+    /// Puts the level back the way it was at the last checkpoint, if there is one.
+    fn respawn(&mut self) -> SimEvent {
+        let Some(checkpoint) = self.checkpoint.clone() else { return SimEvent::Died };
+        let pos = checkpoint.player.pos;
+        self.map = checkpoint.map;
+        self.collision = wall_collision(&self.map);
+        self.entities = checkpoint.entities;
+        let tick = self.tick;
+        self.entities.iter_mut().for_each(|e| if let Behaviour::Enemy(enemy) = &mut e.behaviour { enemy.next_move = tick + enemy.move_ticks; });
+        self.player = Player {
+            ani: Ani::new(self.time(),1.0,vec![pos]),
+            next_move: self.tick + self.player.freeze_ticks,
+            ..checkpoint.player
+        };
+        self.shopping = false;
+        self.moves.clear();
+        self.history.clear();
+        SimEvent::Respawned
+    }
+    /// Digs out a single rock tile and patches the wall edges around it.
+    fn dig(&mut self, pos: (i32,i32)) {
+        set_tile(&mut self.map,&mut self.edits,pos,TileType::Floor);
+        patch_collision(&mut self.collision,&self.map,pos);
+    }
+    /// Opens every gate whose pressure plate has a boulder on it. A gate never closes on the player,
+    /// or on anything solid standing in it.
+    fn update_gates(&mut self) {
+        let player = self.player.tile();
+        self.plates.iter().for_each(|(plate,gate)| {
+            let pressed = matches!(self.map.get(plate), Some(TileType::Boulder));
+            if pressed {
+                set_tile(&mut self.map,&mut self.edits,*gate,TileType::OpenGate);
+            } else if *gate != player && !self.entities.blocks(*gate) {
+                set_tile(&mut self.map,&mut self.edits,*gate,TileType::Gate);
+            }
+        });
+    }
+    fn buy(&mut self, item: ShopItem) -> bool {
+        let Some(&(_,price)) = self.prices.iter().find(|(i,_)| *i == item) else { return false };
+        let player = &mut self.player;
+        let useless = match item {
+            ShopItem::Pickaxe => player.pickaxe,
+            ShopItem::Fuel => player.fuel >= FULL_FUEL,
+            _ => false,
+        };
+        if player.gold < price || useless { return false }
+        player.gold -= price;
+        match item {
+            ShopItem::Health => { player.health += 1; }
+            ShopItem::Fuel => { player.fuel = FULL_FUEL; }
+            ShopItem::Key => { player.keys += 1; }
+            ShopItem::Pickaxe => { player.pickaxe = true; }
+        }
+        true
+    }
+    /// Walls plus everything in the tile map or on it that blocks light, boxed in by the edges of the screen.
+    pub fn dynamic_collision(&self) -> Vec<LineSegment> {
+        let blocking = self.map.iter().filter(|(_,t)| matches!(t,TileType::Boulder | TileType::Gate)).map(|(pos,_)| *pos);
+        let opaque = self.entities.iter().filter(|e| e.collider.opaque).map(|e| e.tile);
+        let obj_collision = blocking.chain(opaque).flat_map(|pos|{
+            let p = tile_pos(pos);
+            let top = LineSegment::new(p,p + vec2(TILE_WIDTH,0));
+            let right = LineSegment::new(p + vec2(TILE_WIDTH,0), p + vec2(TILE_WIDTH,TILE_WIDTH));
+            let bottom = LineSegment::new(p + vec2(TILE_WIDTH,TILE_WIDTH), p + vec2(0,TILE_WIDTH));
+            let left = LineSegment::new(p + vec2(0,TILE_WIDTH), p);
+            [top,right,bottom,left]
+        }).collect::<Vec<_>>();
+
+        let mut collision: Vec<LineSegment> = vec![];
+        let screen = SCREEN;
+        collision.extend(&self.collision);
+        let start = self.player.pos - screen / 2.0;
+        collision.extend(&vec!(
+            LineSegment::new(start,start + vec2(screen.x,0)).reverse_normal(),
+            LineSegment::new(start + vec2(screen.x,0),start + screen).reverse_normal(),
+            LineSegment::new(start + screen,start + vec2(0,screen.y)).reverse_normal(),
+            LineSegment::new(start + vec2(0,screen.y),start).reverse_normal(),
+        ));
+        collision.extend(obj_collision);
+        collision
+    }
+    fn update_enemies(&mut self, collision: &[LineSegment], events: &mut Vec<SimEvent>) {
+        let tick = self.tick;
+        let time = self.time();
+        let player_tile = self.player.tile();
+        let map = &self.map;
+        let mut occupied = self.entities.iter().filter(|e| e.collider.solid).map(|e| e.tile).collect::<Vec<_>>();
+        for entity in self.entities.iter_mut() {
+            let lit = in_light(entity.pos() + vec2(TILE_WIDTH,TILE_WIDTH) / 2f32,&self.light);
+            let Behaviour::Enemy(enemy) = &mut entity.behaviour else { continue };
+            let walkable = |p: (i32,i32)| !occupied.contains(&p) && open_ground(map,p);
+            match enemy.update(tick,entity.tile,player_tile,collision,lit,walkable) {
+                Step::Wait => {}
+                Step::To(next) => {
+                    let duration = enemy.move_ticks as f32 * DT;
+                    if let Some(tile) = occupied.iter_mut().find(|t| **t == entity.tile) { *tile = next; }
+                    entity.step_to(next,time,duration);
+                }
+                Step::Attack => {
+                    self.player.health -= 1;
+                    events.push(SimEvent::Hurt);
+                }
+            }
+        }
+    }
+}
+
+/// Changes a tile, noting what it was in `edits`.
+fn set_tile(map: &mut HashMap<(i32,i32),TileType>, edits: &mut Vec<((i32,i32),TileType)>, pos: (i32,i32), tile: TileType) {
+    if let Some(old) = map.insert(pos,tile) {
+        edits.push((pos,old));
+    }
+}
+
+/// Ground anything can stand on, as long as there's nothing solid on it.
+fn open_ground(map: &HashMap<(i32,i32),TileType>, pos: (i32,i32)) -> bool {
+    matches!(map.get(&pos), Some(TileType::Floor | TileType::Plate | TileType::OpenGate | TileType::Checkpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::parse_map;
+
+    /// Starts a turn based run, so every move happens on the tick it's asked for.
+    fn sim(rows: &str) -> Sim {
+        Sim::new(parse_map(&format!("5\nshop:key=2\n{rows}")).unwrap(),0,1,true)
+    }
+
+    /// Presses right for a tick and lets go, returning what happened.
+    fn step_right(sim: &mut Sim) -> Vec<SimEvent> {
+        let events = sim.step(Input { right: true, ..Input::default() });
+        sim.step(Input::default());
+        events
+    }
+
+    #[test]
+    fn picks_up_gold() {
+        let mut sim = sim("wwww\nwpgw\nwwww");
+        assert!(step_right(&mut sim).contains(&SimEvent::PickedUp(EntityKind::Gold)));
+        assert_eq!(sim.player.gold,1);
+        assert_eq!(sim.player.tile(),(2,1));
+        assert!(sim.entities.is_empty());
+    }
+
+    #[test]
+    fn keys_open_doors() {
+        let mut sim = sim("wwwww\nwpkdw\nwwwww");
+        assert!(step_right(&mut sim).contains(&SimEvent::PickedUp(EntityKind::Key)));
+        assert_eq!(sim.player.keys,1);
+        assert!(step_right(&mut sim).contains(&SimEvent::DoorOpened((3,1))));
+        assert_eq!((sim.player.keys,sim.player.tile()),(0,(3,1)));
+    }
+
+    #[test]
+    fn doors_cost_their_price() {
+        let mut sim = sim("wwww\nwpdw\nwwww");
+        sim.player.gold = 4;
+        step_right(&mut sim);
+        assert_eq!(sim.player.tile(),(1,1));
+        sim.player.gold = 5;
+        assert!(step_right(&mut sim).contains(&SimEvent::DoorOpened((2,1))));
+        assert_eq!((sim.player.gold,sim.player.tile()),(0,(2,1)));
+    }
+
+    #[test]
+    fn enemies_block_the_player() {
+        let mut sim = sim("wwwww\nwplww\nwwwww");
+        step_right(&mut sim);
+        assert_eq!(sim.player.tile(),(1,1));
+        assert!(!sim.walkable((2,1)));
+    }
+
+    #[test]
+    fn spikes_hurt_without_moving() {
+        let mut sim = sim("wwww\nwpsw\nwwww");
+        assert!(step_right(&mut sim).contains(&SimEvent::Hurt));
+        assert_eq!(sim.player.health,4);
+        assert_eq!(sim.player.tile(),(1,1));
+    }
+
+    #[test]
+    fn torch_burns_down_and_the_shop_refills_it() {
+        let mut sim = sim("wwwww\nwp..w\nwwwww");
+        step_right(&mut sim);
+        step_right(&mut sim);
+        assert_eq!(sim.player.fuel,FULL_FUEL - 2);
+        sim.prices.push((ShopItem::Fuel,1));
+        sim.player.gold = 2;
+        assert!(sim.buy(ShopItem::Fuel));
+        assert_eq!((sim.player.fuel,sim.player.gold),(FULL_FUEL,1));
+        assert!(!sim.buy(ShopItem::Fuel),"a full torch can't take more");
+    }
+
+    #[test]
+    fn buying_costs_gold() {
+        let mut sim = sim("wwww\nwp$w\nwwww");
+        sim.player.gold = 3;
+        assert!(step_right(&mut sim).contains(&SimEvent::ShopOpened));
+        assert!(sim.step(Input { buy: Some(ShopItem::Key), ..Input::default() }).contains(&SimEvent::Purchased(ShopItem::Key)));
+        assert!(sim.step(Input { buy: Some(ShopItem::Key), ..Input::default() }).is_empty());
+        assert_eq!((sim.player.gold,sim.player.keys),(1,1));
+    }
+
+    #[test]
+    fn boulders_stop_at_entities() {
+        let mut sim = sim("wwwwww\nwpogdw\nwwwwww");
+        step_right(&mut sim);
+        assert_eq!(sim.player.tile(),(1,1));
+        assert_eq!(sim.map[&(2,1)],TileType::Boulder);
+    }
+
+    #[test]
+    fn boulders_on_plates_hold_gates_open() {
+        let mut sim = sim("wwwwww\nwpo_.w\nwww|ww");
+        step_right(&mut sim);
+        assert_eq!(sim.map[&(3,2)],TileType::OpenGate);
+        step_right(&mut sim);
+        assert_eq!(sim.map[&(4,1)],TileType::Boulder);
+        assert_eq!(sim.map[&(3,2)],TileType::Gate);
+    }
+
+    #[test]
+    fn gates_stay_open_on_solid_entities() {
+        let mut sim = sim("wwwwwww\nwpo_.dw\nwww|www");
+        step_right(&mut sim);
+        sim.entities.iter_mut().for_each(|e| e.tile = (3,2));
+        step_right(&mut sim);
+        assert_eq!(sim.map[&(3,2)],TileType::OpenGate);
+    }
+
+    #[test]
+    fn undo_takes_back_a_checkpoint() {
+        let mut sim = sim("wwwww\nwp.cw\nwwwww");
+        step_right(&mut sim);
+        step_right(&mut sim);
+        assert_eq!(sim.checkpoint.as_ref().map(|c| c.tile),Some((3,1)));
+        sim.step(Input { undo: true, ..Input::default() });
+        assert!(sim.checkpoint.is_none());
+        sim.step(Input { redo: true, ..Input::default() });
+        assert_eq!(sim.checkpoint.as_ref().map(|c| c.tile),Some((3,1)));
+    }
+
+    #[test]
+    fn turns_keep_only_what_they_changed() {
+        let mut sim = sim("wwwwww\nwpg.gw\nwwwwww");
+        step_right(&mut sim);
+        let turn = sim.history.undo().unwrap();
+        assert_eq!(turn.entities.len(),1,"the gold further on stays out of it");
+        assert!(turn.tiles.is_empty() && turn.checkpoint.is_none());
+        sim.replay_turn(&turn,true);
+        assert_eq!((sim.player.gold,sim.entities.len()),(0,2));
+        assert_eq!(sim.entities.get(0).map(|e| e.tile),Some((2,1)));
+        sim.step(Input { redo: true, ..Input::default() });
+        assert_eq!((sim.player.gold,sim.entities.len()),(1,1));
+        assert!(sim.entities.get(0).is_none());
+    }
+
+    #[test]
+    fn dying_goes_back_to_the_checkpoint() {
+        let mut sim = sim("wwwwww\nwpcgrw\nwwwwww");
+        step_right(&mut sim);
+        step_right(&mut sim);
+        sim.player.pickaxe = true;
+        step_right(&mut sim);
+        assert_eq!((sim.player.gold,sim.map[&(4,1)]),(1,TileType::Floor));
+        sim.player.health = 0;
+        assert!(sim.step(Input::default()).contains(&SimEvent::Respawned));
+        assert_eq!((sim.player.tile(),sim.player.gold,sim.player.health),((2,1),0,5));
+        assert_eq!(sim.map[&(4,1)],TileType::Rock);
+        assert_eq!(sim.entities.get(0).map(|e| e.tile),Some((3,1)));
+    }
+
+    #[test]
+    fn dying_without_a_checkpoint_ends_the_run() {
+        let mut sim = sim("wwww\nwp.w\nwwww");
+        sim.player.health = 0;
+        assert!(sim.step(Input::default()).contains(&SimEvent::Died));
+    }
+
+    #[test]
+    fn reaching_the_exit_escapes() {
+        let mut sim = sim("wwww\nwpew\nwwww");
+        assert!(step_right(&mut sim).contains(&SimEvent::Escaped));
+        assert!(sim.escaped);
+    }
+}
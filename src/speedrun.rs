@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::sim::TICKS_PER_SECOND;
+use crate::storage::data_dir;
+
+/// Timer for a run through the whole campaign. Counts simulation ticks, so times match replays exactly.
+#[derive(Debug, Default)]
+pub struct SpeedrunTimer {
+    pub ticks: u64,
+    pub running: bool,
+    /// Time at each level exit so far this run.
+    pub splits: Vec<u64>,
+    /// Splits of the personal best, empty if there isn't one yet.
+    pub best: Vec<u64>,
+    /// Where a new personal best is written. Only timers from [`SpeedrunTimer::load`] have one.
+    file: Option<PathBuf>,
+}
+impl SpeedrunTimer {
+    pub fn load() -> Self {
+        let best = fs::read_to_string(splits_path()).map(|data| {
+            data.lines().filter_map(|line| line.trim().parse().ok()).collect()
+        }).unwrap_or_default();
+        Self { best, file: Some(splits_path()), ..Default::default() }
+    }
+    pub fn start(&mut self) {
+        self.running = true;
+    }
+    pub fn reset(&mut self) {
+        self.ticks = 0;
+        self.running = false;
+        self.splits.clear();
+    }
+    pub fn tick(&mut self) {
+        if self.running {
+            self.ticks += 1;
+        }
+    }
+    pub fn split(&mut self) {
+        self.splits.push(self.ticks);
+    }
+    /// Stops the timer and keeps the run as the personal best if it beat it. Returns true if it did.
+    pub fn finish(&mut self) -> bool {
+        self.running = false;
+        let beat = self.best.len() != self.splits.len() || self.best.last().is_none_or(|best| self.ticks < *best);
+        if beat {
+            self.best = self.splits.clone();
+        }
+        if let Some(file) = self.file.as_ref().filter(|_| beat) {
+            let data = self.best.iter().map(u64::to_string).collect::<Vec<_>>().join("\n");
+            if let Err(e) = fs::create_dir_all(data_dir()).and_then(|()| fs::write(file,data)) {
+                println!("Couldn't save splits: {e}");
+            }
+        }
+        beat
+    }
+    /// How far ahead (negative) or behind the personal best the run is at split `i`.
+    pub fn delta(&self, i: usize) -> Option<i64> {
+        let time = *self.splits.get(i)?;
+        let best = *self.best.get(i)?;
+        Some(time as i64 - best as i64)
+    }
+}
+
+/// Formats ticks as `m:ss.cc`.
+pub fn format_ticks(ticks: u64) -> String {
+    let centis = ticks * 100 / TICKS_PER_SECOND;
+    format!("{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
+fn splits_path() -> PathBuf {
+    data_dir().join("splits.txt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A timer that stopped at its last split, racing against `best`.
+    fn timer(splits: Vec<u64>, best: Vec<u64>) -> SpeedrunTimer {
+        SpeedrunTimer { ticks: *splits.last().unwrap(), running: true, splits, best, file: None }
+    }
+
+    #[test]
+    fn formats_minutes_seconds_and_hundredths() {
+        assert_eq!(format_ticks(0),"0:00.00");
+        assert_eq!(format_ticks(1),"0:00.01");
+        assert_eq!(format_ticks(61 * TICKS_PER_SECOND + TICKS_PER_SECOND / 2),"1:01.50");
+    }
+
+    #[test]
+    fn faster_runs_become_the_best() {
+        let mut timer = timer(vec![90,180],vec![100,200]);
+        assert!(timer.finish());
+        assert!(!timer.running);
+        assert_eq!(timer.best,vec![90,180]);
+    }
+
+    #[test]
+    fn slower_runs_keep_the_old_best() {
+        let mut timer = timer(vec![90,250],vec![100,200]);
+        assert!(!timer.finish());
+        assert_eq!(timer.best,vec![100,200]);
+    }
+
+    #[test]
+    fn deltas_compare_each_split() {
+        let timer = timer(vec![90,210],vec![100,200]);
+        assert_eq!(timer.delta(0),Some(-10));
+        assert_eq!(timer.delta(1),Some(10));
+        assert_eq!(timer.delta(2),None);
+        assert_eq!(SpeedrunTimer::default().delta(0),None,"nothing to compare against without a best");
+    }
+}
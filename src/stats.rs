@@ -0,0 +1,47 @@
+use crate::entity::{Entities, EntityKind};
+use crate::sim::TICKS_PER_SECOND;
+
+/// How a run through one level went, collected by the cave as it plays and shown on the results screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    pub level_name: String,
+    pub gold: i32,
+    pub gold_total: i32,
+    pub doors: i32,
+    pub doors_total: i32,
+    pub damage: i32,
+    pub steps: u32,
+    pub ticks: u64,
+    /// Time in ticks to beat for each star, from the level's `stars:` directive.
+    pub star_ticks: Vec<u64>,
+}
+impl RunStats {
+    pub fn new(level_name: String, entities: &Entities, star_ticks: Vec<u64>) -> Self {
+        Self {
+            level_name,
+            gold_total: count(entities,EntityKind::Gold),
+            doors_total: count(entities,EntityKind::Door),
+            star_ticks,
+            ..Default::default()
+        }
+    }
+    /// Recounts the gold and doors after the map was put back to an earlier state by a respawn or undo,
+    /// steps and damage stay counted.
+    pub fn rewound(&mut self, entities: &Entities) {
+        self.gold = self.gold_total - count(entities,EntityKind::Gold);
+        self.doors = self.doors_total - count(entities,EntityKind::Door);
+    }
+    /// One star for each threshold the run was at least as fast as.
+    pub fn stars(&self) -> usize {
+        self.star_ticks.iter().filter(|t| self.ticks <= **t).count()
+    }
+}
+
+/// Parses a `stars:` directive, the times in seconds for one, two and three stars, like `90,60,40`.
+pub fn parse_stars(value: &str) -> Vec<u64> {
+    value.split(',').filter_map(|secs| secs.trim().parse::<u64>().ok()).map(|secs| secs * TICKS_PER_SECOND).collect()
+}
+
+fn count(entities: &Entities, kind: EntityKind) -> i32 {
+    entities.count(kind) as i32
+}
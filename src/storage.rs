@@ -0,0 +1,39 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP: &str = "cave_escape";
+
+/// Where saves, ghosts and records are kept, e.g. `~/.local/share/cave_escape`.
+/// Falls back to a `data` folder next to the game if no home directory can be found.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join(APP)
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join(APP)
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".local").join("share").join(APP)
+    }
+    PathBuf::from("data")
+}
+
+/// Where settings and key bindings are kept, e.g. `~/.config/cave_escape`.
+/// Falls back to a `config` folder next to the game if no home directory can be found.
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        return PathBuf::from(dir).join(APP)
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join(APP)
+    }
+    if let Some(home) = env::var_os("HOME") {
+        return PathBuf::from(home).join(".config").join(APP)
+    }
+    PathBuf::from("config")
+}
+
+/// The fastest run of a level, replayed as a ghost. Its length in ticks is the level's best time.
+pub fn ghost_path(level_hash: u64) -> PathBuf {
+    data_dir().join("ghosts").join(format!("{level_hash:016x}.cavr"))
+}
@@ -1,20 +1,96 @@
 use neo_granseal::mesh::{FillStyle, rect_filled};
+use std::cell::RefCell;
+use std::process::exit;
+use std::rc::Rc;
 use neo_granseal::prelude::*;
 use crate::cave::CaveEvent;
-use crate::ui::{Ui, UiImageStyle, UiLabelStyle, UiThing};
+use crate::input::{Action, Bindings};
+use crate::save::latest_slot;
+use crate::scenes::{SceneId, Transition, TRANSITION_TIME};
+use crate::settings::Settings;
+use crate::ui::{Ui, UiEvent, UiImageStyle, UiLabelStyle, UiThing};
 
-#[derive(Default)]
 pub struct TitleScreen {
+    settings: Rc<RefCell<Settings>>,
+    bindings: Rc<RefCell<Bindings>>,
+    gran: Option<Image>,
     ui: UiThing,
 }
+impl TitleScreen {
+    pub fn new(settings: Rc<RefCell<Settings>>, bindings: Rc<RefCell<Bindings>>) -> Self {
+        Self { settings, bindings, gran: None, ui: UiThing::default() }
+    }
+    fn build_ui(&mut self, core: &NGCore) {
+        let Some(gran) = self.gran else { return };
+        let scale = self.settings.borrow().ui_scale;
+        let font = Font::new(64f32);
+        let exit = font.text("Exit Game",100f32 * scale).size() + vec2(8,8);
+        let mut children = vec![
+            Ui::Label {
+                name: "title".to_string(),
+                position: vec2(16,75),
+                text: "Cave Escape".to_string(),
+                children: vec![],
+                style: UiLabelStyle {
+                    text_scale: 150f32 * scale,
+                    ..Default::default()
+                },
+            },
+        ];
+        // Without a save there's nothing to continue.
+        let items = if latest_slot().is_some() {
+            vec![("continue","Continue"),("start","New Game"),("levels","Level Select"),("options","Options"),("exit","Exit Game")]
+        } else {
+            vec![("start","Start Game"),("levels","Level Select"),("options","Options"),("exit","Exit Game")]
+        };
+        let mut y = 260f32;
+        for (name,text) in items {
+            children.push(menu_item(name,text,y,gran,exit.y,scale));
+            y += 100f32 * scale;
+        }
+        let mut ui = UiThing::default();
+        ui.build(&Ui::Frame {
+            name: "title".to_string(),
+            position: vec2(32,32),
+            size: vec2(core.config.width - 64,core.config.height - 64),
+            children,
+            style: Default::default(),
+        });
+        self.ui = ui;
+    }
+}
 
 impl NeoGransealEventHandler for TitleScreen {
     fn event(&mut self, core: &mut NGCore, event: Event) {
         if self.ui.event(core,&event) {return}
         match event {
-            Event::KeyEvent {key, state} => {
-                if state == KeyState::Pressed && key == Key::Space {
-                    core.event(CaveEvent::SetScene(1));
+            // Same as picking Start, the cave starts over and switches itself in.
+            Event::KeyEvent { key, state: KeyState::Pressed } if self.bindings.borrow().is(Action::Confirm,key) => {
+                core.event(CaveEvent::NewGame);
+            }
+            Event::Custom(msg) => {
+                if let Ok(msg) = msg.downcast::<CaveEvent>() {
+                    match *msg {
+                        CaveEvent::Ui(UiEvent::MousePressed { id, .. }) => {
+                            match id.as_str() {
+                                "start" => core.event(CaveEvent::NewGame),
+                                "continue" => core.event(CaveEvent::ContinueGame),
+                                "levels" => {
+                                    core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+                                    core.event(CaveEvent::Push(SceneId::LevelSelect));
+                                }
+                                "options" => {
+                                    core.event(CaveEvent::Transition(Transition::slide(TRANSITION_TIME)));
+                                    core.event(CaveEvent::Push(SceneId::Options));
+                                }
+                                "exit" => exit(0),
+                                _ => {}
+                            }
+                        }
+                        // Shown as well, a game may have been saved since, which makes Continue appear.
+                        CaveEvent::SettingsChanged | CaveEvent::Shown => { self.build_ui(core); }
+                        _ => {}
+                    }
                 }
             }
             Event::MouseWheel(x,y) => {
@@ -30,69 +106,33 @@ impl NeoGransealEventHandler for TitleScreen {
                 g.draw_mesh(&mb.build(),Vec2::ZERO);
             }
             Event::Load => {
-                let gran = core.load_image("assets/granseal.png", true).unwrap();
-                let font = Font::default();
-                let exit = font.text("Exit Game",100f32).size() + vec2(8,8);
-                self.ui.build(&Ui::Frame {
-                    name: "title".to_string(),
-                    position: vec2(32,32),
-                    size: vec2(core.config.width - 64,core.config.height - 64),
-                    children: vec![
-                        Ui::Label {
-                            name: "title".to_string(),
-                            position: vec2(16,75),
-                            text: "Cave Escape".to_string(),
-                            children: vec![],
-                            style: UiLabelStyle {
-                                text_scale: 150f32,
-                                ..Default::default()
-                            },
-                        },
-                        Ui::Label {
-                            name: "start".to_string(),
-                            position: vec2(100,300),
-                            text: "Start Game".to_string(),
-                            children: vec![
-                                Ui::Image {
-                                    name: "granseal".to_string(),
-                                    position: vec2(-32,exit.y / 2.0 - 8.0),
-                                    image: gran,
-                                    style: UiImageStyle::default(),
-                                    children: vec![],
-                                    size: Some(vec2(16,16)),
-                                },
-                            ],
-                            style: UiLabelStyle {
-                                hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
-                                text_scale: 100f32,
-                                ..Default::default()
-                            },
-                        },
-                        Ui::Label {
-                            name: "exit".to_string(),
-                            position: vec2(100,420),
-                            text: "Exit Game".to_string(),
-                            children: vec![
-                                Ui::Image {
-                                    name: "granseal2".to_string(),
-                                    position: vec2(-32, exit.y / 2.0 - 8.0),
-                                    image: gran,
-                                    style: UiImageStyle::default(),
-                                    children: vec![],
-                                    size: Some(vec2(16,16)),
-                                },
-                            ],
-                            style: UiLabelStyle {
-                                hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
-                                text_scale: 100f32,
-                                ..Default::default()
-                            },
-                        }
-                    ],
-                    style: Default::default(),
-                });
+                self.gran = Some(core.load_image("assets/granseal.png", true).unwrap());
+                self.build_ui(core);
             }
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+fn menu_item(name: &str, text: &str, y: f32, image: Image, height: f32, scale: f32) -> Ui {
+    Ui::Label {
+        name: name.to_string(),
+        position: vec2(100f32,y),
+        text: text.to_string(),
+        children: vec![
+            Ui::Image {
+                name: format!("{name}_granseal"),
+                position: vec2(-32,height / 2.0 - 8.0),
+                image,
+                style: UiImageStyle::default(),
+                children: vec![],
+                size: Some(vec2(16,16)),
+            },
+        ],
+        style: UiLabelStyle {
+            hover_text: FillStyle::FadeLeft(Color::ORANGE_RED,Color::RED),
+            text_scale: 100f32 * scale,
+            ..Default::default()
+        },
+    }
+}
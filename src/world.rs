@@ -0,0 +1,616 @@
+//! The cave itself: tiles, the player, what's in the level and what can be seen from where.
+//! Nothing in here draws, so it can be used and tested without a window, see [`crate::render`] for that.
+use std::collections::HashMap;
+use neo_granseal::prelude::*;
+use neo_granseal::util::{LineSegment, raycast};
+use crate::enemy::EnemyKind;
+use crate::entity::{Behaviour, Entities, EntityKind};
+use crate::stats::parse_stars;
+
+pub const TILE_WIDTH: i32 = 28;
+/// Top left corner of a tile, in pixels.
+pub fn tile_pos(tile: (i32,i32)) -> Vec2 {
+    vec2(tile.0 * TILE_WIDTH, tile.1 * TILE_WIDTH)
+}
+pub const SCREEN: Vec2 = Vec2 {
+    x: TILE_WIDTH as f32 * 30.0,
+    y: TILE_WIDTH as f32 * 30.0,
+};
+/// Steps a full torch lasts. It burns down by one every time the player moves, and the shop refills it.
+pub const FULL_FUEL: i32 = 400;
+
+/// Half the size of the lit box around the player. It shrinks from the edge of the screen
+/// as the torch burns down, to a few tiles when it's out.
+pub fn light_reach(fuel: i32) -> Vec2 {
+    let embers = vec2(TILE_WIDTH * 3,TILE_WIDTH * 3);
+    let burning = fuel.clamp(0,FULL_FUEL) as f32 / FULL_FUEL as f32;
+    embers + (SCREEN * 0.5 - embers) * burning
+}
+
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileType {
+    Floor,
+    Wall,
+    Rock,
+    Warp,
+    Exit,
+    Shop,
+    Boulder,
+    Plate,
+    Gate,
+    OpenGate,
+    Checkpoint,
+}
+impl TileType {
+    pub const ALL: [TileType; 11] = [
+        TileType::Floor, TileType::Wall, TileType::Rock, TileType::Warp, TileType::Exit,
+        TileType::Shop, TileType::Boulder, TileType::Plate, TileType::Gate, TileType::OpenGate,
+        TileType::Checkpoint,
+    ];
+    /// Character for this tile in save files. Mostly the same as the level glyphs.
+    pub fn glyph(self) -> char {
+        match self {
+            TileType::Floor => '.',
+            TileType::Wall => 'w',
+            TileType::Rock => 'r',
+            TileType::Warp => '*',
+            TileType::Exit => 'e',
+            TileType::Shop => '$',
+            TileType::Boulder => 'o',
+            TileType::Plate => '_',
+            TileType::Gate => '|',
+            TileType::OpenGate => '/',
+            TileType::Checkpoint => 'c',
+        }
+    }
+    pub fn from_glyph(c: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.glyph() == c)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShopItem {
+    Health,
+    Fuel,
+    Key,
+    Pickaxe,
+}
+impl ShopItem {
+    pub const ALL: [ShopItem; 4] = [ShopItem::Health, ShopItem::Fuel, ShopItem::Key, ShopItem::Pickaxe];
+
+    /// Name used in level data and as the ui id suffix, e.g. `buy_health`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShopItem::Health => "health",
+            ShopItem::Fuel => "fuel",
+            ShopItem::Key => "key",
+            ShopItem::Pickaxe => "pickaxe",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.name() == name)
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            ShopItem::Health => "Health",
+            ShopItem::Fuel => "Torch Fuel",
+            ShopItem::Key => "Key",
+            ShopItem::Pickaxe => "Pickaxe",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub pos: Vec2,
+    pub ani: Ani<Vec2>,
+    pub gold: i32,
+    pub health: i32,
+    pub fuel: i32,
+    pub keys: i32,
+    pub pickaxe: bool,
+    /// Tick the player may move again on.
+    pub next_move: u64,
+    pub freeze_ticks: u64,
+}
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            pos: Default::default(),
+            ani: Ani::new(0.0,1.0,vec![]),
+            gold: 0,
+            health: 0,
+            fuel: FULL_FUEL,
+            keys: 0,
+            pickaxe: false,
+            next_move: 0,
+            freeze_ticks: 9,
+        }
+    }
+    pub fn tile(&self) -> (i32,i32) {
+        (self.pos.x.floor() as i32 / TILE_WIDTH, self.pos.y.floor() as i32 / TILE_WIDTH)
+    }
+}
+impl Default for Player {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+pub struct MapInfo {
+    pub(crate) tiles: HashMap<(i32, i32),TileType>,
+    pub(crate) collision: Vec<LineSegment>,
+    pub(crate) player_start: Vec2,
+    pub(crate) entities: Entities,
+    pub(crate) prices: Vec<(ShopItem,i32)>,
+    /// Pressure plates and the gate each one opens, linked in the order they appear in the map.
+    pub(crate) plates: Vec<((i32,i32),(i32,i32))>,
+    /// Times in ticks for each star on the results screen, see [`parse_stars`].
+    pub(crate) stars: Vec<u64>,
+}
+
+/// Map rows, skipping the door costs on the first line and any `name:value` directives.
+pub(crate) fn map_rows(data: &str) -> impl Iterator<Item = &str> {
+    data.lines().skip(1).filter(|line| !line.contains(':'))
+}
+
+/// Finds a `name:value` directive line in the level header.
+fn directive<'a>(data: &'a str, name: &'a str) -> Option<&'a str> {
+    directives(data,name).next()
+}
+
+/// Every `name:value` directive with the given name, in the order they appear.
+fn directives<'a>(data: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> {
+    data.lines().skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .filter(move |(n,_)| *n == name)
+        .map(|(_,value)| value.trim())
+}
+
+/// Parses a list of tile coordinates like `12,30 30,30 30,36`.
+fn parse_tiles(value: &str) -> Vec<(i32,i32)> {
+    value.split_whitespace().filter_map(|tile| {
+        let (x,y) = tile.split_once(',')?;
+        Some((x.parse().ok()?,y.parse().ok()?))
+    }).collect()
+}
+
+/// Reads a level: the tiles and everything in them. Builds no meshes, see [`crate::render::floor_mesh`] and [`crate::render::wall_mesh`].
+/// Fails with a message saying what's wrong if the level is malformed.
+pub fn parse_map(data: &str) -> Result<MapInfo,String> {
+    let mut tiles = HashMap::new();
+    let header = data.lines().next().ok_or("the level is empty")?;
+    let door_g = header.split(',').filter(|_| !header.trim().is_empty())
+        .map(|v| v.trim().parse::<i32>().map_err(|_| format!("bad door price '{v}' on the first line")))
+        .collect::<Result<Vec<_>,_>>()?;
+    let mut door = 0;
+
+    let prices = directive(data,"shop").map(|shop| {
+        shop.split(',').filter_map(|entry| {
+            let (name,price) = entry.split_once('=')?;
+            Some((ShopItem::from_name(name.trim())?,price.trim().parse::<i32>().ok()?))
+        }).collect::<Vec<_>>()
+    }).unwrap_or_default();
+
+    let mut player_start = Vec2::ZERO;
+    let mut entities = Entities::default();
+    let mut plates = vec![];
+    let mut gates = vec![];
+    let mut routes = directives(data,"patrol").map(parse_tiles);
+
+    for (y,line) in map_rows(data).enumerate() {
+        for (x,c) in line.chars().enumerate() {
+            let tile = (x as i32,y as i32);
+            let t = match c {
+                'w' => TileType::Wall,
+                'r' => TileType::Rock,
+                '.' | 'b' => TileType::Floor,
+                '$' => TileType::Shop,
+                'o' => TileType::Boulder,
+                '_' => {
+                    plates.push(tile);
+                    TileType::Plate
+                }
+                '|' => {
+                    gates.push(tile);
+                    TileType::Gate
+                }
+                'c' => TileType::Checkpoint,
+                'e' => TileType::Exit,
+                'p' => {
+                    player_start = vec2(x as i32 * TILE_WIDTH, y as i32 * TILE_WIDTH);
+                    TileType::Floor
+                }
+                c => {
+                    let Some(kind) = EntityKind::from_glyph(c) else { continue };
+                    let mut entity = kind.spawn(tile);
+                    match &mut entity.behaviour {
+                        Behaviour::Door { cost } => {
+                            *cost = *door_g.get(door)
+                                .ok_or_else(|| format!("door at {},{} has no price, the first line only has {}",tile.0,tile.1,door_g.len()))?;
+                            door += 1;
+                        }
+                        Behaviour::Enemy(enemy) if enemy.kind == EnemyKind::Patrol => {
+                            enemy.route = routes.next().unwrap_or_default();
+                        }
+                        _ => {}
+                    }
+                    entities.push(entity);
+                    TileType::Floor
+                }
+            };
+            tiles.insert(tile,t);
+        }
+    }
+
+    let collision = wall_collision(&tiles);
+    if plates.len() != gates.len() {
+        return Err(format!("every pressure plate needs a gate, there are {} plates and {} gates",plates.len(),gates.len()));
+    }
+    let plates = plates.into_iter().zip(gates).collect::<Vec<_>>();
+
+    Ok(MapInfo {
+        tiles,
+        collision,
+        player_start,
+        entities,
+        prices,
+        plates,
+        stars: directive(data,"stars").map(parse_stars).unwrap_or_default(),
+    })
+}
+
+fn is_wall(tiles: &HashMap<(i32,i32),TileType>, pos: (i32,i32)) -> bool {
+    matches!(tiles.get(&pos), Some(TileType::Wall | TileType::Rock))
+}
+
+/// Merges the outline of every wall tile into as few line segments as possible.
+/// Walls are visited row by row, so each one can extend the edges of its northern and western neighbours.
+pub fn wall_collision(tiles: &HashMap<(i32,i32),TileType>) -> Vec<LineSegment> {
+    #[derive(Default)]
+    struct Cell {
+        north: Option<usize>,
+        west: Option<usize>,
+        east: Option<usize>,
+        south: Option<usize>,
+    }
+    let mut cells: HashMap<(i32,i32),Cell> = HashMap::new();
+    let mut walls = tiles.keys().filter(|p| is_wall(tiles,**p)).copied().collect::<Vec<_>>();
+    walls.sort_by_key(|&(x,y)| (y,x));
+
+    let mut collision: Vec<LineSegment> = vec![];
+
+    walls.iter().for_each(|&(x,y)| {
+        let (wcell,ecell,ncell,scell) = ((x-1,y),(x+1,y),(x,y-1),(x,y+1));
+        let pos = vec2(x * TILE_WIDTH, y * TILE_WIDTH);
+        let mut cell = Cell::default();
+        // Should I make a western edge?
+        if !is_wall(tiles,wcell) {
+            // Is there a western edge north of me I can extend?
+            if !is_wall(tiles,ncell) {
+                // No, let's make one.
+                let edge = LineSegment::new(pos + vec2(0,TILE_WIDTH), pos );
+                collision.push(edge);
+                cell.west = Some(collision.len() - 1);
+            } else {
+                // Yes, Does it have an edge to extend?
+                if let Some(edge) = cells.get(&ncell).and_then(|c| c.west) {
+                    collision[edge].begin.y += TILE_WIDTH as f32;
+                    cell.west = Some(edge);
+                } else {
+                    // No, let's make one.
+                    let edge = LineSegment::new(pos + vec2(0,TILE_WIDTH), pos );
+                    collision.push(edge);
+                    cell.west = Some(collision.len() - 1);
+                }
+            }
+        }
+        // Northern Edge
+        if !is_wall(tiles,ncell) {
+            if !is_wall(tiles,wcell) {
+                let edge = LineSegment::new(pos , pos+ vec2(TILE_WIDTH, 0));
+                collision.push(edge);
+                cell.north = Some(collision.len() - 1);
+            } else if let Some(edge) = cells.get(&wcell).and_then(|c| c.north) {
+                collision[edge].end.x += TILE_WIDTH as f32;
+                cell.north = Some(edge);
+            } else {
+                let edge = LineSegment::new(pos , pos+ vec2(TILE_WIDTH, 0));
+                collision.push(edge);
+                cell.north = Some(collision.len() - 1);
+            }
+        }
+        // Eastern Edge
+        if !is_wall(tiles,ecell) {
+            if !is_wall(tiles,ncell) {
+                let edge = LineSegment::new(pos + vec2(TILE_WIDTH,0), pos + vec2(TILE_WIDTH, TILE_WIDTH));
+                collision.push(edge);
+                cell.east = Some(collision.len() - 1);
+            } else if let Some(edge) = cells.get(&ncell).and_then(|c| c.east) {
+                collision[edge].end.y += TILE_WIDTH as f32;
+                cell.east = Some(edge);
+            } else {
+                let edge = LineSegment::new(pos + vec2(TILE_WIDTH,0), pos + vec2(TILE_WIDTH, TILE_WIDTH));
+                collision.push(edge);
+                cell.east = Some(collision.len() - 1);
+            }
+        }
+        // Southern Edge
+        if !is_wall(tiles,scell) {
+            if !is_wall(tiles,wcell) {
+                let edge = LineSegment::new(pos + vec2(TILE_WIDTH,TILE_WIDTH), pos + vec2(0, TILE_WIDTH));
+                collision.push(edge);
+                cell.south = Some(collision.len() - 1);
+            } else if let Some(edge) = cells.get(&wcell).and_then(|c| c.south) {
+                collision[edge].begin.x += TILE_WIDTH as f32;
+                cell.south = Some(edge);
+            } else {
+                let edge = LineSegment::new(pos + vec2(TILE_WIDTH,TILE_WIDTH), pos + vec2(0, TILE_WIDTH));
+                collision.push(edge);
+                cell.south = Some(collision.len() - 1);
+            }
+        }
+
+        cells.insert((x,y),cell);
+    });
+
+    collision
+}
+
+/// The four sides of a tile, each with the neighbour it faces. They wind the same way as the
+/// edges from [`wall_collision`]: west goes up, north right, east down and south left.
+fn sides((x,y): (i32,i32)) -> [((i32,i32),Vec2,Vec2); 4] {
+    let pos = vec2(x * TILE_WIDTH, y * TILE_WIDTH);
+    let t = TILE_WIDTH;
+    [
+        ((x-1,y),pos + vec2(0,t),pos),
+        ((x,y-1),pos,pos + vec2(t,0)),
+        ((x+1,y),pos + vec2(t,0),pos + vec2(t,t)),
+        ((x,y+1),pos + vec2(t,t),pos + vec2(0,t)),
+    ]
+}
+
+/// Whether `b` lies on the line through `edge`.
+fn on_line(edge: &LineSegment, b: Vec2) -> bool {
+    let (d,v) = (edge.end - edge.begin,b - edge.begin);
+    d.x * v.y - d.y * v.x == 0.0
+}
+
+/// Whether `edge` runs the same way as the side from `begin` to `end`.
+fn same_way(edge: &LineSegment, begin: Vec2, end: Vec2) -> bool {
+    let (d,v) = (edge.end - edge.begin,end - begin);
+    d.x * v.y - d.y * v.x == 0.0 && d.x * v.x + d.y * v.y > 0.0
+}
+
+/// Takes one tile side out of the merged edge it's part of, splitting the edge around it.
+fn remove_side(collision: &mut Vec<LineSegment>, begin: Vec2, end: Vec2) {
+    let along = |edge: &LineSegment, p: Vec2| {
+        let (d,v) = (edge.end - edge.begin,p - edge.begin);
+        (d.x * v.x + d.y * v.y) / (d.x * d.x + d.y * d.y)
+    };
+    let Some(i) = collision.iter().position(|edge| {
+        same_way(edge,begin,end) && on_line(edge,begin) && (0.0..=1.0).contains(&along(edge,begin)) && (0.0..=1.0).contains(&along(edge,end))
+    }) else { return };
+    let edge = collision.swap_remove(i);
+    if edge.begin != begin {
+        collision.push(LineSegment::new(edge.begin,begin));
+    }
+    if edge.end != end {
+        collision.push(LineSegment::new(end,edge.end));
+    }
+}
+
+/// Adds one tile side, joining it onto the edges it continues.
+fn add_side(collision: &mut Vec<LineSegment>, begin: Vec2, end: Vec2) {
+    let before = collision.iter().position(|edge| edge.end == begin && same_way(edge,begin,end));
+    let after = collision.iter().position(|edge| edge.begin == end && same_way(edge,begin,end));
+    match (before,after) {
+        (Some(i),Some(j)) => {
+            collision[i].end = collision[j].end;
+            collision.swap_remove(j);
+        }
+        (Some(i),None) => { collision[i].end = end; }
+        (None,Some(j)) => { collision[j].begin = begin; }
+        (None,None) => { collision.push(LineSegment::new(begin,end)); }
+    }
+}
+
+/// Patches edges from [`wall_collision`] after the tile at `pos` turned into or out of a wall, already changed in `tiles`.
+/// Only the sides of that tile and of its four neighbours can have changed, the rest of the edges are left alone.
+pub fn patch_collision(collision: &mut Vec<LineSegment>, tiles: &HashMap<(i32,i32),TileType>, pos: (i32,i32)) {
+    let wall = is_wall(tiles,pos);
+    // Sides between the tile and open neighbours, and the neighbouring walls' sides facing it. Only one of the two exists.
+    let own = sides(pos).into_iter().filter(|(n,_,_)| !is_wall(tiles,*n)).map(|(_,b,e)| (b,e)).collect::<Vec<_>>();
+    let facing = sides(pos).into_iter().filter(|(n,_,_)| is_wall(tiles,*n))
+        .flat_map(|(n,_,_)| sides(n).into_iter().filter(|(m,_,_)| *m == pos).map(|(_,b,e)| (b,e)))
+        .collect::<Vec<_>>();
+    let (gone,new) = if wall { (facing,own) } else { (own,facing) };
+    gone.into_iter().for_each(|(b,e)| remove_side(collision,b,e));
+    new.into_iter().for_each(|(b,e)| add_side(collision,b,e));
+}
+
+/// The outline of everything visible from `origin` no further than `reach` away on either axis, sorted by angle around it.
+pub fn visibility_polygon(origin: &Vec2, walls: &[LineSegment], reach: Vec2) -> Vec<Vec2> {
+    let cull = reach * 1.1;
+    let (a,b) = (*origin - reach,*origin + reach);
+    let walls = walls.iter().copied().chain([
+        LineSegment::new(a,vec2(b.x,a.y)).reverse_normal(),
+        LineSegment::new(vec2(b.x,a.y),b).reverse_normal(),
+        LineSegment::new(b,vec2(a.x,b.y)).reverse_normal(),
+        LineSegment::new(vec2(a.x,b.y),a).reverse_normal(),
+    ]).collect::<Vec<_>>();
+    let walls = walls.as_slice();
+
+    let targets = walls.iter().map(|w|
+        w.begin
+    ).filter(|t| (t.x - origin.x).abs() <= cull.x && (t.y - origin.y).abs() <= cull.y ).collect::<Vec<_>>();
+
+    let mut hits = vec![];
+    targets.iter().for_each(|v| {
+        let target_angle = (*v - *origin).angle2();
+        let (angle1,angle2) = (target_angle - 0.0005,target_angle + 0.0005);
+        if let Some(wall_hit) = raycast(*origin,angle_vec2(angle1),walls) {
+            hits.push(wall_hit.hit);
+        }
+        if let Some(wall_hit) = raycast(*origin,angle_vec2(angle2),walls) {
+            hits.push(wall_hit.hit);
+        }
+    });
+
+    hits.sort_by(|a,b| {
+        (*origin - *a).angle2().total_cmp(&(*origin - *b).angle2())
+    });
+    hits
+}
+
+/// Even-odd test against a visibility polygon from [`visibility_polygon`].
+pub fn in_light(point: Vec2, polygon: &[Vec2]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i,a) in polygon.iter().enumerate() {
+        let b = polygon[j];
+        if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "\
+3,7
+shop:health=2,key=4
+stars:30,20,12
+patrol:3,1 3,2
+wwwwww
+wp.g.w
+w.dm_w
+w|r$ew
+wwwwww";
+
+    #[test]
+    fn parses_tiles_and_start() {
+        let map = parse_map(LEVEL).unwrap();
+        assert_eq!(map.tiles.len(),30);
+        assert_eq!(map.tiles[&(0,0)],TileType::Wall);
+        assert_eq!(map.tiles[&(3,1)],TileType::Floor);
+        assert_eq!(map.tiles[&(2,3)],TileType::Rock);
+        assert_eq!(map.tiles[&(4,3)],TileType::Exit);
+        assert_eq!(map.tiles[&(3,2)],TileType::Floor);
+        assert_eq!(map.player_start,vec2(TILE_WIDTH,TILE_WIDTH));
+    }
+
+    #[test]
+    fn parses_directives() {
+        let map = parse_map(LEVEL).unwrap();
+        assert_eq!(map.prices,vec![(ShopItem::Health,2),(ShopItem::Key,4)]);
+        assert_eq!(map.stars,parse_stars("30,20,12"));
+        assert_eq!(map.plates,vec![((4,2),(1,3))]);
+    }
+
+    #[test]
+    fn parses_entities() {
+        let map = parse_map(LEVEL).unwrap();
+        let kinds = map.entities.iter().map(|e| (e.tile,e.kind)).collect::<Vec<_>>();
+        assert_eq!(kinds,vec![((3,1),EntityKind::Gold),((2,2),EntityKind::Door),((3,2),EntityKind::Patrol)]);
+        assert!(matches!(map.entities.at((2,2)).unwrap().behaviour,Behaviour::Door { cost: 3 }));
+        let patrol = map.entities.at((3,2)).and_then(|e| e.enemy()).unwrap();
+        assert_eq!(patrol.route,vec![(3,1),(3,2)]);
+    }
+
+    #[test]
+    fn malformed_levels_are_errors() {
+        assert!(parse_map("").is_err());
+        assert!(parse_map("5,x\nwpw").is_err());
+        assert!(parse_map("5\nwdpdw").is_err());
+        assert!(parse_map("\nwp_w").is_err());
+        assert!(parse_map("\nwp_||w").is_err());
+        assert!(parse_map("\nwpw").is_ok());
+    }
+
+    #[test]
+    fn campaign_levels_parse() {
+        for level in crate::levels::LEVELS {
+            assert!(parse_map(level.data).is_ok(),"{}",level.name);
+        }
+    }
+
+    #[test]
+    fn light_shrinks_as_the_torch_burns_down() {
+        assert_eq!(light_reach(FULL_FUEL).x,SCREEN.x * 0.5);
+        assert!(light_reach(FULL_FUEL / 2).x < light_reach(FULL_FUEL).x);
+        assert_eq!(light_reach(0).x,(TILE_WIDTH * 3) as f32);
+        assert_eq!(light_reach(-5).x,light_reach(0).x);
+    }
+
+    #[test]
+    fn walls_merge_into_edges() {
+        let mut tiles = HashMap::new();
+        tiles.insert((0,0),TileType::Wall);
+        assert_eq!(wall_collision(&tiles).len(),4);
+        tiles.insert((1,0),TileType::Wall);
+        tiles.insert((2,0),TileType::Rock);
+        assert_eq!(wall_collision(&tiles).len(),4);
+        tiles.insert((0,1),TileType::Wall);
+        assert_eq!(wall_collision(&tiles).len(),6);
+    }
+
+    /// Edges as a sorted list of end points, so two sets of them can be compared whatever their order.
+    fn edges(collision: &[LineSegment]) -> Vec<(i32,i32,i32,i32)> {
+        let mut edges = collision.iter().map(|e| (e.begin.x as i32,e.begin.y as i32,e.end.x as i32,e.end.y as i32)).collect::<Vec<_>>();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn patched_edges_match_a_rebuild() {
+        let map = parse_map("\nwwwwww\nwrrrrw\nwrprrw\nwrrrrw\nwwwwww").unwrap();
+        let mut tiles = map.tiles;
+        let mut collision = map.collision;
+        // Into the middle of the rock, along its edge and out through the wall, then filled back in.
+        for pos in [(3,2),(1,2),(1,1),(0,2),(4,3),(4,2)] {
+            tiles.insert(pos,TileType::Floor);
+            patch_collision(&mut collision,&tiles,pos);
+            assert_eq!(edges(&collision),edges(&wall_collision(&tiles)),"digging {pos:?}");
+        }
+        for pos in [(1,1),(0,2),(3,2)] {
+            tiles.insert(pos,TileType::Rock);
+            patch_collision(&mut collision,&tiles,pos);
+            assert_eq!(edges(&collision),edges(&wall_collision(&tiles)),"filling {pos:?}");
+        }
+    }
+
+    #[test]
+    fn digging_through_the_campaign_matches_a_rebuild() {
+        for level in crate::levels::LEVELS {
+            let map = parse_map(level.data).unwrap();
+            let (mut tiles,mut collision) = (map.tiles,map.collision);
+            // Every wall in a scattered order, checking along the way.
+            let mut walls = tiles.keys().filter(|p| is_wall(&tiles,**p)).copied().collect::<Vec<_>>();
+            walls.sort_by_key(|&(x,y)| ((x * 7 + y * 13) % 17,x,y));
+            for (i,pos) in walls.into_iter().enumerate() {
+                tiles.insert(pos,TileType::Floor);
+                patch_collision(&mut collision,&tiles,pos);
+                if i % 50 == 0 {
+                    assert_eq!(edges(&collision),edges(&wall_collision(&tiles)),"{} at {pos:?}",level.name);
+                }
+            }
+            assert!(collision.is_empty());
+        }
+    }
+
+    #[test]
+    fn light_inside_polygon() {
+        let square = [vec2(0,0),vec2(10,0),vec2(10,10),vec2(0,10)];
+        assert!(in_light(vec2(5,5),&square));
+        assert!(!in_light(vec2(15,5),&square));
+        assert!(!in_light(vec2(5,-1),&square));
+    }
+}